@@ -3,16 +3,21 @@ dotenv.config();
 import * as cdk from "aws-cdk-lib";
 import * as iam from "aws-cdk-lib/aws-iam";
 import { Construct } from "constructs";
+import { getEnv } from "../bin/util";
 import { RustFunction } from "@cdklabs/aws-lambda-rust";
 import * as apigwv2 from "aws-cdk-lib/aws-apigatewayv2";
 import * as acm from "aws-cdk-lib/aws-certificatemanager";
 import { HttpLambdaIntegration } from "aws-cdk-lib/aws-apigatewayv2-integrations";
 import * as logs from "aws-cdk-lib/aws-logs";
+import * as s3 from "aws-cdk-lib/aws-s3";
 import * as sns from "aws-cdk-lib/aws-sns";
+import * as ssm from "aws-cdk-lib/aws-ssm";
 import * as subscriptions from "aws-cdk-lib/aws-sns-subscriptions";
 import { Alarm, TreatMissingData } from "aws-cdk-lib/aws-cloudwatch";
 import { SnsAction } from "aws-cdk-lib/aws-cloudwatch-actions";
 import { Architecture } from "aws-cdk-lib/aws-lambda";
+import * as events from "aws-cdk-lib/aws-events";
+import * as targets from "aws-cdk-lib/aws-events-targets";
 import {
   GithubActionsIdentityProvider,
   GithubActionsRole,
@@ -59,15 +64,103 @@ export class TrashcalCdkStack extends cdk.Stack {
       retention: logs.RetentionDays.ONE_MONTH,
     });
 
+    // a month of logs is a month of home addresses, so keep only the street name unless told otherwise
+    const logRedaction = process.env.LOG_REDACTION ?? "street";
+
+    // where links in calendars, digests and redirects point: this deployment unless told otherwise
+    const baseUrl = process.env.BASE_URL ?? `https://${props.domainName}`;
+
+    // Schedule history for /{id}/changes, which has to outlive any one lambda container
+    const historyBucket = new s3.Bucket(this, "trashcal-history", {
+      blockPublicAccess: s3.BlockPublicAccess.BLOCK_ALL,
+      encryption: s3.BucketEncryption.S3_MANAGED,
+      enforceSSL: true,
+    });
+
     // Create the rust lambda
     const trashcal = new RustFunction(this, "trashcal-lambda", {
       entry: "../lambda",
+      binaryName: "trashcal",
       architecture: Architecture.ARM_64,
       logGroup,
+      // room for a couple of retries against a slow city server, but under API Gateway's 30s
+      timeout: cdk.Duration.seconds(25),
       environment: {
         AWS_LAMBDA_LOG_FORMAT: "json",
+        TRASHCAL_LOG_REDACTION: logRedaction,
+        TRASHCAL_HISTORY_BUCKET: historyBucket.bucketName,
+        // CloudFront doesn't forward the viewer Host, so tell the lambda where it lives
+        TRASHCAL_BASE_URL: baseUrl,
+        TRASHCAL_ALLOWED_ORIGINS: process.env.ALLOWED_ORIGINS ?? "*",
+        // the collection ID /canary scrapes to make sure the city's page still parses
+        TRASHCAL_CANARY_ID: process.env.CANARY_ID ?? "a4Ot0000001E8i4EAC",
       },
     });
+    historyBucket.grantReadWrite(trashcal);
+
+    // Day-before reminders, only if there's somebody to remind
+    if (process.env.WEBHOOKS) {
+      const notifier = new RustFunction(this, "trashcal-notifier", {
+        entry: "../lambda",
+        binaryName: "notifier",
+        architecture: Architecture.ARM_64,
+        logGroup,
+        timeout: cdk.Duration.minutes(1),
+        environment: {
+          AWS_LAMBDA_LOG_FORMAT: "json",
+          TRASHCAL_LOG_REDACTION: logRedaction,
+          TRASHCAL_HISTORY_BUCKET: historyBucket.bucketName,
+          TRASHCAL_WEBHOOKS: process.env.WEBHOOKS,
+        },
+      });
+      historyBucket.grantReadWrite(notifier);
+
+      // 01:00 UTC is the early evening in San Diego, right when the bins go out
+      new events.Rule(this, "trashcal-notifier-schedule", {
+        schedule: events.Schedule.cron({ hour: "1", minute: "0" }),
+        targets: [new targets.LambdaFunction(notifier)],
+      });
+    }
+
+    // Weekly email digests, only if there's somebody to mail
+    if (process.env.DIGESTS) {
+      // the SMTP URL has the mail server's password in it, so it lives in a SecureString
+      // (created by hand, since CloudFormation can't) that the lambda reads when it runs
+      const smtpUrl = ssm.StringParameter.fromSecureStringParameterAttributes(
+        this,
+        "trashcal-smtp-url",
+        { parameterName: process.env.SMTP_URL_PARAMETER ?? "/trashcal/smtp-url" }
+      );
+
+      const digest = new RustFunction(this, "trashcal-digest", {
+        entry: "../lambda",
+        binaryName: "digest",
+        architecture: Architecture.ARM_64,
+        logGroup,
+        timeout: cdk.Duration.minutes(1),
+        environment: {
+          AWS_LAMBDA_LOG_FORMAT: "json",
+          TRASHCAL_LOG_REDACTION: logRedaction,
+          TRASHCAL_HISTORY_BUCKET: historyBucket.bucketName,
+          TRASHCAL_BASE_URL: baseUrl,
+          TRASHCAL_DIGESTS: process.env.DIGESTS,
+          TRASHCAL_SMTP_URL_PARAMETER: smtpUrl.parameterName,
+          TRASHCAL_DIGEST_FROM: getEnv("DIGEST_FROM"),
+        },
+      });
+      historyBucket.grantReadWrite(digest);
+      smtpUrl.grantRead(digest);
+
+      // Sunday evening in San Diego, ahead of the week
+      new events.Rule(this, "trashcal-digest-schedule", {
+        schedule: events.Schedule.cron({
+          weekDay: "MON",
+          hour: "1",
+          minute: "0",
+        }),
+        targets: [new targets.LambdaFunction(digest)],
+      });
+    }
 
     const trashcalIntegration = new HttpLambdaIntegration(
       "trashcal-integration",
@@ -77,10 +170,22 @@ export class TrashcalCdkStack extends cdk.Stack {
     // create the api gateway endpoint
     const api = new apigwv2.HttpApi(this, "trashcal-api", {});
 
-    // only one route
+    // the calendar itself, plus the webcal:// redirect for one-click subscribing
+    // and the lightweight per-ID endpoints like /{id}/next. Every method goes to the
+    // lambda, which answers GET, HEAD and OPTIONS and turns the rest away with a 405.
     api.addRoutes({
       path: "/{id}",
-      methods: [apigwv2.HttpMethod.GET],
+      methods: [apigwv2.HttpMethod.ANY],
+      integration: trashcalIntegration,
+    });
+    api.addRoutes({
+      path: "/{id}/{proxy+}",
+      methods: [apigwv2.HttpMethod.ANY],
+      integration: trashcalIntegration,
+    });
+    api.addRoutes({
+      path: "/subscribe/{id}",
+      methods: [apigwv2.HttpMethod.ANY],
       integration: trashcalIntegration,
     });
 
@@ -103,7 +208,9 @@ export class TrashcalCdkStack extends cdk.Stack {
         // UseOriginCacheControlHeaders / Amplify policies all force Host into the
         // cache key, which makes CloudFront forward the viewer Host and API Gateway
         // answer 403 Forbidden. Format is selected by URL suffix, so the path —
-        // always in the cache key — keeps JSON and iCal separate. TTL follows the
+        // always in the cache key — keeps JSON and iCal separate. The query string
+        // and Origin aren't in the key, so the render options rely on the lambda's
+        // canonical redirect rather than on separate cache entries. TTL follows the
         // Expires header the Lambda emits.
         cachePolicy: cloudfront.CachePolicy.CACHING_OPTIMIZED,
       },
@@ -112,14 +219,12 @@ export class TrashcalCdkStack extends cdk.Stack {
 
     const metricNamespace = "trashcal";
 
-    // create a metric for counting total calendars
-    new logs.MetricFilter(this, "trashcal-total", {
-      logGroup,
-      metricNamespace,
-      metricName: "total",
-      filterPattern: logs.FilterPattern.allTerms("Returning calendar"),
-      metricValue: "1",
-    });
+    // The lambda publishes its own metrics to this namespace with Embedded Metric Format
+    // (lambda/src/metrics.rs): total (by format), upstream_latency, parse_duration,
+    // pickups_parsed, stale_served and errors (by error). A panic never gets that far, so
+    // panics are still counted from the logs. The EMF total replaced the "trashcal-total"
+    // log filter on "Returning calendar", so point any dashboard or alarm built on that
+    // filter at the EMF metric (same name and namespace, no dimensions) instead.
 
     // create a metric for rust panics (hi Paul! 👋)
     const panicFilter = new logs.MetricFilter(
@@ -0,0 +1,232 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+/// Words that start the unit part of a street line, as in "1234 AGATE ST APT 5"
+const UNIT_DESIGNATORS: [&str; 8] = ["APT", "UNIT", "STE", "SUITE", "SPC", "BLDG", "LOT", "RM"];
+
+/// Directions stay shouty, so "NW" doesn't come out as "Nw"
+const DIRECTIONS: [&str; 8] = ["N", "S", "E", "W", "NE", "NW", "SE", "SW"];
+
+/// The city's one line address ("1234 AGATE ST, San Diego, CA 92109") in pieces, tidied up for
+/// showing to people
+#[derive(Serialize, Debug, Default, Clone, Eq, PartialEq)]
+pub struct Address {
+    /// The house number, e.g. "1234" or "1234-1/2"
+    pub number: Option<String>,
+    /// The street without the number, e.g. "Agate St"
+    pub street: String,
+    /// e.g. "Apt 5" or "#5"
+    pub unit: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub zip: Option<String>,
+}
+
+fn title_case(s: &str) -> String {
+    s.split_whitespace()
+        .map(|word| {
+            let upper = word.to_uppercase();
+            if DIRECTIONS.contains(&upper.as_str()) {
+                return upper;
+            }
+            // "30TH" is "30th" and "MC-COY" is "Mc-Coy"
+            let mut capitalize = true;
+            word.chars()
+                .map(|c| {
+                    let c = if capitalize {
+                        c.to_ascii_uppercase()
+                    } else {
+                        c.to_ascii_lowercase()
+                    };
+                    capitalize = !c.is_alphanumeric();
+                    c
+                })
+                .collect()
+        })
+        .join(" ")
+}
+
+fn is_zip(word: &str) -> bool {
+    let zip = word.split('-').next().unwrap_or_default();
+    zip.len() == 5 && zip.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn is_state(word: &str) -> bool {
+    word.len() == 2 && word.bytes().all(|b| b.is_ascii_alphabetic())
+}
+
+fn is_unit_start(word: &str) -> bool {
+    word.starts_with('#') || UNIT_DESIGNATORS.contains(&word.to_uppercase().as_str())
+}
+
+impl Address {
+    /// Picks apart whatever the city gave us. Anything it can't place stays in the street, so
+    /// nothing gets lost.
+    pub fn parse(s: &str) -> Address {
+        let mut parts = s
+            .split(',')
+            .map(|part| part.split_whitespace().collect_vec())
+            .filter(|words| !words.is_empty())
+            .collect_vec();
+        let mut address = Address::default();
+
+        // "CA 92109" at the very end, with or without a comma before it. Without a ZIP, only a
+        // part of its own counts as the state, so "1234 AGATE CT" stays in Court, not Connecticut.
+        let count = parts.len();
+        if let Some(last) = parts.last_mut() {
+            let has_zip = last.last().is_some_and(|w| is_zip(w));
+            if has_zip {
+                address.zip = last.pop().map(str::to_string);
+            }
+            let own_part = count > 1 && last.len() == 1;
+            if (has_zip || own_part) && last.last().is_some_and(|w| is_state(w)) {
+                address.state = last.pop().map(str::to_uppercase);
+            }
+        }
+        parts.retain(|words| !words.is_empty());
+
+        let mut parts = parts.into_iter();
+        let mut street = parts.next().unwrap_or_default();
+        let rest = parts.collect_vec();
+
+        // whatever's between the street and the state is the city, unless it's a unit
+        let mut units = vec![];
+        let mut city = vec![];
+        for part in rest {
+            if is_unit_start(part[0]) {
+                units.push(part);
+            } else {
+                city = part;
+            }
+        }
+
+        // a unit tacked onto the street line, e.g. "1234 AGATE ST APT 5" or "1234 AGATE ST #5"
+        if let Some(at) = street.iter().skip(1).position(|w| is_unit_start(w)) {
+            units.insert(0, street.split_off(at + 1));
+        }
+
+        if street
+            .first()
+            .is_some_and(|w| w.starts_with(|c: char| c.is_ascii_digit()))
+        {
+            address.number = Some(street.remove(0).to_uppercase());
+        }
+        address.street = title_case(&street.join(" "));
+        let unit = title_case(&units.into_iter().flatten().join(" "));
+        address.unit = (!unit.is_empty()).then_some(unit);
+        address.city = (!city.is_empty()).then(|| title_case(&city.join(" ")));
+        address
+    }
+
+    /// "1234 Agate St Apt 5", for naming things
+    pub fn street_line(&self) -> String {
+        [
+            self.number.as_deref(),
+            Some(self.street.as_str()),
+            self.unit.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|s| !s.is_empty())
+        .join(" ")
+    }
+}
+
+impl FromStr for Address {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Address::parse(s))
+    }
+}
+
+impl Display for Address {
+    /// "1234 Agate St Apt 5, San Diego, CA 92109"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state_zip = [self.state.as_deref(), self.zip.as_deref()]
+            .into_iter()
+            .flatten()
+            .join(" ");
+        let line = [Some(self.street_line()), self.city.clone(), Some(state_zip)]
+            .into_iter()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .join(", ");
+        write!(f, "{line}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Address;
+
+    fn some(s: &str) -> Option<String> {
+        Some(s.to_string())
+    }
+
+    #[test]
+    fn city_format() {
+        let address = Address::parse("1234 AGATE ST, San Diego, CA 92109");
+        assert_eq!(
+            address,
+            Address {
+                number: some("1234"),
+                street: "Agate St".to_string(),
+                unit: None,
+                city: some("San Diego"),
+                state: some("CA"),
+                zip: some("92109"),
+            }
+        );
+        assert_eq!(address.street_line(), "1234 Agate St");
+        assert_eq!(address.to_string(), "1234 Agate St, San Diego, CA 92109");
+    }
+
+    #[test]
+    fn units() {
+        for s in [
+            "1234 AGATE ST APT 5, San Diego, CA 92109",
+            "1234 AGATE ST, APT 5, SAN DIEGO, CA 92109",
+            "1234  agate st   apt 5 ,San Diego,CA 92109",
+        ] {
+            let address = Address::parse(s);
+            assert_eq!(address.street, "Agate St", "{s}");
+            assert_eq!(address.unit, some("Apt 5"), "{s}");
+            assert_eq!(address.city, some("San Diego"), "{s}");
+            assert_eq!(
+                address.to_string(),
+                "1234 Agate St Apt 5, San Diego, CA 92109",
+                "{s}"
+            );
+        }
+
+        let address = Address::parse("1234 AGATE ST #5, San Diego, CA 92109");
+        assert_eq!(address.unit, some("#5"));
+    }
+
+    #[test]
+    fn odd_formatting() {
+        let address = Address::parse("1234-1/2 NW 30TH ST, San Diego, CA 92104-1234");
+        assert_eq!(address.number, some("1234-1/2"));
+        assert_eq!(address.street, "NW 30th St");
+        assert_eq!(address.zip, some("92104-1234"));
+
+        // no commas at all still finds the state and ZIP, but can't tell the city from the street
+        let address = Address::parse("1234 AGATE ST CA 92109");
+        assert_eq!(address.street, "Agate St");
+        assert_eq!(address.state, some("CA"));
+        assert_eq!(address.zip, some("92109"));
+
+        // and a street with no number or anything else is just a street
+        let address = Address::parse("AGATE ST");
+        assert_eq!(address.number, None);
+        assert_eq!(address.street, "Agate St");
+        assert_eq!(address.to_string(), "Agate St");
+        assert_eq!(Address::parse("1234 AGATE CT").street, "Agate Ct");
+
+        assert_eq!(Address::parse("").to_string(), "");
+    }
+}
@@ -0,0 +1,68 @@
+use std::fmt::Display;
+
+/// The public trashcal, used when nothing else tells us where we're deployed
+pub const DEFAULT_BASE_URL: &str = "https://stabbylambda.com/trashcal";
+
+/// Set this to wherever your trashcal is publicly reachable
+pub const BASE_URL_VAR: &str = "TRASHCAL_BASE_URL";
+
+/// Where trashcal is served from; every link we hand out hangs off of this
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BaseUrl(String);
+
+impl BaseUrl {
+    pub fn new(url: &str) -> BaseUrl {
+        BaseUrl(url.trim_end_matches('/').to_string())
+    }
+
+    /// The configured URL, or the public trashcal. The request's Host is never trusted for
+    /// this: behind CloudFront it's the API Gateway domain, and anybody can send any Host.
+    pub fn from_env() -> BaseUrl {
+        BaseUrl::from_config(std::env::var(BASE_URL_VAR).ok().as_deref())
+    }
+
+    fn from_config(configured: Option<&str>) -> BaseUrl {
+        match configured {
+            Some(url) if !url.is_empty() => BaseUrl::new(url),
+            _ => BaseUrl::default(),
+        }
+    }
+
+    pub fn calendar_url(&self, id: &str) -> String {
+        format!("{}/{id}", self.0)
+    }
+}
+
+impl Default for BaseUrl {
+    fn default() -> Self {
+        BaseUrl::new(DEFAULT_BASE_URL)
+    }
+}
+
+impl Display for BaseUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BaseUrl;
+
+    #[test]
+    fn configuration_wins() {
+        let url = BaseUrl::from_config(Some("https://trash.example.com/"));
+        assert_eq!(url.calendar_url("foo"), "https://trash.example.com/foo");
+    }
+
+    #[test]
+    fn falls_back_to_default() {
+        for configured in [None, Some("")] {
+            let url = BaseUrl::from_config(configured);
+            assert_eq!(
+                url.calendar_url("foo"),
+                "https://stabbylambda.com/trashcal/foo"
+            );
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use ::trashcal::digest::digest_handler;
+use ::trashcal::telemetry;
+use lambda_runtime::{diagnostic::Diagnostic, run, service_fn, tower::ServiceExt, Error};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    telemetry::init("trashcal-digest");
+    let handler = service_fn(|event| async {
+        let response = digest_handler(event).await;
+        telemetry::flush();
+        response
+    });
+    run(handler.map_err(std::convert::Into::<Diagnostic>::into)).await
+}
@@ -0,0 +1,14 @@
+use ::trashcal::notify::notify_handler;
+use ::trashcal::telemetry;
+use lambda_runtime::{diagnostic::Diagnostic, run, service_fn, tower::ServiceExt, Error};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    telemetry::init("trashcal-notifier");
+    let handler = service_fn(|event| async {
+        let response = notify_handler(event).await;
+        telemetry::flush();
+        response
+    });
+    run(handler.map_err(std::convert::Into::<Diagnostic>::into)).await
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use lambda_runtime::tracing::{info, warn};
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use crate::collection_id::CollectionId;
+use crate::next::today;
+use crate::pickup::PickupType;
+use crate::pickup_calendar::PickupCalendar;
+use crate::redact;
+use crate::trashcal::scrape;
+
+/// The collection ID the canary scrapes
+pub const CANARY_ID_VAR: &str = "TRASHCAL_CANARY_ID";
+
+/// 1234 Agate St, the address from the README
+pub const DEFAULT_CANARY_ID: &str = "a4Ot0000001E8i4EAC";
+
+/// Trash is weekly and recycling and greens alternate weeks, so every kind of pickup should
+/// show up within two weeks of today
+pub const WINDOW_DAYS: u64 = 14;
+
+/// How long a report gets reused, so hitting /canary over and over doesn't hit the city (or
+/// trip everybody's circuit breaker) over and over
+pub const REPORT_TTL: Duration = Duration::from_secs(60);
+
+static LAST_REPORT: Mutex<Option<(Instant, CanaryReport)>> = Mutex::new(None);
+
+/// What the canary found, for uptime monitors and for whoever gets paged
+#[derive(Debug, Clone, Serialize)]
+pub struct CanaryReport {
+    pub ok: bool,
+    pub id: String,
+    pub checked_at: DateTime<Utc>,
+    pub window_start: NaiveDate,
+    pub window_end: NaiveDate,
+    pub address: Option<String>,
+    /// How many pickups parsed out of the page, in or out of the window
+    pub pickups: usize,
+    /// The first pickup of each type inside the window
+    pub next: BTreeMap<String, Option<NaiveDate>>,
+    /// Types with no pickup inside the window
+    pub missing: Vec<String>,
+    /// Why the scrape itself failed
+    pub error: Option<String>,
+}
+
+impl CanaryReport {
+    pub fn check(
+        id: &str,
+        result: anyhow::Result<PickupCalendar>,
+        today: NaiveDate,
+        checked_at: DateTime<Utc>,
+    ) -> CanaryReport {
+        let window_end = today
+            .checked_add_days(Days::new(WINDOW_DAYS))
+            .unwrap_or(today);
+
+        let mut report = CanaryReport {
+            ok: false,
+            id: id.to_string(),
+            checked_at,
+            window_start: today,
+            window_end,
+            address: None,
+            pickups: 0,
+            next: BTreeMap::new(),
+            missing: vec![],
+            error: None,
+        };
+
+        let calendar = match result {
+            Ok(calendar) => calendar,
+            Err(e) => {
+                report.error = Some(format!("{e:#}"));
+                return report;
+            }
+        };
+
+        for pickup_type in PickupType::iter() {
+            let name = pickup_type.display_string(false);
+            let next = calendar
+                .pickups
+                .iter()
+                .filter(|p| p.name == pickup_type && (today..=window_end).contains(&p.date))
+                .map(|p| p.date)
+                .min();
+            if next.is_none() {
+                report.missing.push(name.clone());
+            }
+            report.next.insert(name, next);
+        }
+
+        report.ok = report.missing.is_empty();
+        report.address = Some(calendar.address);
+        report.pickups = calendar.pickups.len();
+        report
+    }
+}
+
+/// Scrapes the canary ID straight from the city. This skips the last good calendar that
+/// `trashcal()` falls back on, since that would hide exactly the breakage we're looking for.
+pub async fn run_canary() -> CanaryReport {
+    let id = std::env::var(CANARY_ID_VAR).unwrap_or_else(|_| DEFAULT_CANARY_ID.to_string());
+    let result = match id.parse::<CollectionId>() {
+        Ok(collection_id) => scrape(&collection_id).await.map_err(Into::into),
+        Err(e) => Err(e.into()),
+    };
+
+    let report = CanaryReport::check(&id, result, today(), Utc::now());
+    if report.ok {
+        info!(message = "Canary passed", pickups = report.pickups);
+    } else {
+        warn!(
+            message = "Canary failed",
+            error = ?report.error.as_deref().map(|e| redact::text(e, &id)),
+            missing = ?report.missing
+        );
+    }
+    report
+}
+
+/// The last report while it's fresh, or a new one, along with how much longer it's good for
+pub async fn latest_report() -> (CanaryReport, Duration) {
+    let cached = LAST_REPORT
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|(at, report)| (report.clone(), REPORT_TTL.saturating_sub(at.elapsed())))
+        .filter(|(_, ttl)| !ttl.is_zero());
+    if let Some(cached) = cached {
+        return cached;
+    }
+
+    let report = run_canary().await;
+    *LAST_REPORT.lock().unwrap() = Some((Instant::now(), report.clone()));
+    (report, REPORT_TTL)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+
+    use super::CanaryReport;
+    use crate::error::Error;
+    use crate::fixtures::{calendar, date};
+    use crate::pickup::PickupType;
+
+    #[test]
+    fn every_type_in_window() {
+        let result = Ok(calendar(&[
+            (PickupType::Trash, 6),
+            (PickupType::Recyclables, 6),
+            (PickupType::Organics, 13),
+            (PickupType::Trash, 13),
+        ]));
+
+        let report = CanaryReport::check("a4Ot0000001E8i4EAC", result, date(3), Utc::now());
+        assert!(report.ok);
+        assert_eq!(report.pickups, 4);
+        assert_eq!(report.next["Trash"], Some(date(6)));
+        assert_eq!(report.next["Organics"], Some(date(13)));
+        assert!(report.missing.is_empty());
+    }
+
+    #[test]
+    fn missing_and_out_of_window_types_fail() {
+        let result = Ok(calendar(&[
+            (PickupType::Trash, 6),
+            // in the past
+            (PickupType::Recyclables, 1),
+            // too far out
+            (PickupType::Organics, 30),
+        ]));
+
+        let report = CanaryReport::check("a4Ot0000001E8i4EAC", result, date(3), Utc::now());
+        assert!(!report.ok);
+        assert_eq!(report.missing, vec!["Recyclables", "Organics"]);
+        assert_eq!(report.next["Recyclables"], None);
+    }
+
+    #[test]
+    fn scrape_failure_fails() {
+        let result = Err(Error::MaintenancePage.into());
+
+        let report = CanaryReport::check("a4Ot0000001E8i4EAC", result, date(3), Utc::now());
+        assert!(!report.ok);
+        assert_eq!(
+            report.error.as_deref(),
+            Some("The city is down for maintenance")
+        );
+        assert_eq!(report.address, None);
+    }
+}
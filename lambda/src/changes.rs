@@ -0,0 +1,511 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, ensure, Result};
+use aws_sdk_s3::primitives::ByteStream;
+use chrono::{DateTime, NaiveDate, Utc};
+use itertools::Itertools;
+use lambda_runtime::tracing::info;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::pickup::{Pickup, PickupType};
+use crate::pickup_calendar::PickupCalendar;
+use crate::s3;
+
+/// The S3 bucket per-ID history lives in, so it survives cold starts
+pub const HISTORY_BUCKET_VAR: &str = "TRASHCAL_HISTORY_BUCKET";
+
+/// Where per-ID history lives without a bucket. Lambda's /tmp only survives while the
+/// container is warm, so this is really for running locally.
+pub const HISTORY_DIR_VAR: &str = "TRASHCAL_HISTORY_DIR";
+
+/// How many detected changes we hang on to per ID
+const MAX_CHANGES: usize = 20;
+
+/// How many times to reload and try again when another container saves the same history first
+const MAX_SAVE_ATTEMPTS: usize = 3;
+
+/// Pickups more than a week apart are a different pickup, not a moved one (recycling
+/// switching to the opposite week is exactly a week)
+const MAX_MOVE_DAYS: i64 = 7;
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub struct MovedPickup {
+    pub name: PickupType,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+/// What changed between two scrapes of the same calendar
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Default)]
+pub struct ScheduleDiff {
+    pub added: Vec<Pickup>,
+    pub removed: Vec<Pickup>,
+    pub moved: Vec<MovedPickup>,
+}
+
+impl ScheduleDiff {
+    /// Compares the dates both calendars know about. The city only shows upcoming pickups,
+    /// so old pickups falling off the front and new ones appearing at the end aren't changes.
+    pub fn between(old: &[Pickup], new: &[Pickup]) -> ScheduleDiff {
+        let dates = |pickups: &[Pickup]| pickups.iter().map(|p| p.date).minmax().into_option();
+        let (Some((old_start, old_end)), Some((new_start, new_end))) = (dates(old), dates(new))
+        else {
+            return ScheduleDiff::default();
+        };
+        let start = old_start.max(new_start);
+        let end = old_end.min(new_end);
+        let in_window = |p: &&Pickup| p.date >= start && p.date <= end;
+
+        let old = old.iter().filter(in_window).collect_vec();
+        let new = new.iter().filter(in_window).collect_vec();
+        let mut removed = old
+            .iter()
+            .filter(|p| !new.contains(p))
+            .map(|p| **p)
+            .collect_vec();
+        let mut added = new
+            .iter()
+            .filter(|p| !old.contains(p))
+            .map(|p| **p)
+            .collect_vec();
+
+        // a removal and the nearest addition of the same type close by is one pickup moving
+        let mut moved = vec![];
+        removed.retain(|r| {
+            let distance = |a: &Pickup| (a.date - r.date).num_days().abs();
+            let matching = added
+                .iter()
+                .enumerate()
+                .filter(|(_, a)| a.name == r.name && distance(a) <= MAX_MOVE_DAYS)
+                .min_by_key(|(_, a)| distance(a))
+                .map(|(i, _)| i);
+
+            match matching {
+                Some(i) => {
+                    let a = added.remove(i);
+                    moved.push(MovedPickup {
+                        name: r.name,
+                        from: r.date,
+                        to: a.date,
+                    });
+                    false
+                }
+                None => true,
+            }
+        });
+
+        ScheduleDiff {
+            added,
+            removed,
+            moved,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct ScheduleChange {
+    pub detected_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub diff: ScheduleDiff,
+}
+
+/// The last pickups we saw for an ID and the changes we've noticed along the way. Only the
+/// pickups are kept, since nobody's address needs to sit in storage to spot a schedule change.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Default)]
+pub struct History {
+    pub snapshot: Option<Vec<Pickup>>,
+    pub changes: Vec<ScheduleChange>,
+}
+
+impl History {
+    /// Remember the new calendar's pickups, returning what changed since the last ones
+    pub fn record(
+        &mut self,
+        calendar: PickupCalendar,
+        now: DateTime<Utc>,
+    ) -> Option<&ScheduleChange> {
+        let diff = self
+            .snapshot
+            .as_ref()
+            .map(|old| ScheduleDiff::between(old, &calendar.pickups))
+            .filter(|diff| !diff.is_empty());
+        self.snapshot = Some(calendar.pickups);
+
+        let diff = diff?;
+        self.changes.insert(
+            0,
+            ScheduleChange {
+                detected_at: now,
+                diff,
+            },
+        );
+        self.changes.truncate(MAX_CHANGES);
+        self.changes.first()
+    }
+}
+
+/// The ID turns into a file name or key, so it had better not be able to go anywhere else
+fn file_name(id: &str) -> Result<String> {
+    ensure!(
+        !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric()),
+        Error::IdError(id.to_string())
+    );
+    Ok(format!("{id}.json"))
+}
+
+/// History stored as one JSON file per ID
+#[derive(Debug, Clone)]
+pub struct FileHistory {
+    dir: PathBuf,
+}
+
+impl FileHistory {
+    pub fn new(dir: impl Into<PathBuf>) -> FileHistory {
+        FileHistory { dir: dir.into() }
+    }
+
+    pub fn from_env() -> FileHistory {
+        let dir = std::env::var(HISTORY_DIR_VAR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("trashcal"));
+        FileHistory::new(dir)
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        Ok(self.dir.join(file_name(id)?))
+    }
+
+    pub fn load(&self, id: &str) -> Result<History> {
+        let path = self.path(id)?;
+        match std::fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(History::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, id: &str, history: &History) -> Result<()> {
+        let path = self.path(id)?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(path, serde_json::to_string(history)?)?;
+        Ok(())
+    }
+}
+
+/// History stored as one JSON object per ID in an S3 bucket
+#[derive(Debug, Clone)]
+pub struct S3History {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+}
+
+impl S3History {
+    pub fn new(client: aws_sdk_s3::Client, bucket: &str) -> S3History {
+        S3History {
+            client,
+            bucket: bucket.to_string(),
+        }
+    }
+
+    pub async fn load(&self, id: &str) -> Result<History> {
+        Ok(self.load_versioned(id).await?.0)
+    }
+
+    /// The history along with its ETag, so saving it can tell whether somebody beat us to it
+    pub async fn load_versioned(&self, id: &str) -> Result<(History, Option<String>)> {
+        let response = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(file_name(id)?)
+            .send()
+            .await;
+
+        match response {
+            Ok(object) => {
+                let etag = object.e_tag;
+                let json = object.body.collect().await?.into_bytes();
+                Ok((serde_json::from_slice(&json)?, etag))
+            }
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => {
+                Ok((History::default(), None))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Saves only if the object is still the one with `etag` (or still isn't there at all),
+    /// returning false when another container saved first
+    pub async fn save_if_unchanged(
+        &self,
+        id: &str,
+        history: &History,
+        etag: Option<&str>,
+    ) -> Result<bool> {
+        let request = self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(file_name(id)?)
+            .content_type("application/json")
+            .body(ByteStream::from(serde_json::to_vec(history)?));
+        let request = match etag {
+            Some(etag) => request.if_match(etag),
+            None => request.if_none_match("*"),
+        };
+
+        match request.send().await {
+            Ok(_) => Ok(true),
+            Err(e) if s3::lost_race(&e) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Wherever history is kept: S3 when there's a bucket, files otherwise
+#[derive(Debug, Clone)]
+pub enum HistoryStore {
+    File(FileHistory),
+    S3(S3History),
+}
+
+impl HistoryStore {
+    pub async fn from_env() -> HistoryStore {
+        match std::env::var(HISTORY_BUCKET_VAR) {
+            Ok(bucket) => HistoryStore::S3(S3History::new(s3::client().await.clone(), &bucket)),
+            Err(_) => HistoryStore::File(FileHistory::from_env()),
+        }
+    }
+
+    pub async fn load(&self, id: &str) -> Result<History> {
+        match self {
+            HistoryStore::File(store) => store.load(id),
+            HistoryStore::S3(store) => store.load(id).await,
+        }
+    }
+
+    async fn load_versioned(&self, id: &str) -> Result<(History, Option<String>)> {
+        match self {
+            HistoryStore::File(store) => Ok((store.load(id)?, None)),
+            HistoryStore::S3(store) => store.load_versioned(id).await,
+        }
+    }
+
+    /// Local files are only for running locally, so there's nobody to race with there
+    async fn save_if_unchanged(
+        &self,
+        id: &str,
+        history: &History,
+        etag: Option<&str>,
+    ) -> Result<bool> {
+        match self {
+            HistoryStore::File(store) => store.save(id, history).map(|_| true),
+            HistoryStore::S3(store) => store.save_if_unchanged(id, history, etag).await,
+        }
+    }
+
+    /// Adds a freshly scraped calendar to its ID's history, starting over if another
+    /// container saved the same history in the meantime
+    pub async fn record(&self, calendar: &PickupCalendar) -> Result<()> {
+        for _ in 0..MAX_SAVE_ATTEMPTS {
+            let (mut history, etag) = self.load_versioned(&calendar.id).await?;
+            let change = history.record(calendar.clone(), Utc::now()).cloned();
+            if self
+                .save_if_unchanged(&calendar.id, &history, etag.as_deref())
+                .await?
+            {
+                if let Some(change) = change {
+                    info!(message = "Schedule changed", diff = ?change.diff);
+                }
+                return Ok(());
+            }
+        }
+        bail!("History kept changing while we were saving it")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+
+    use super::{FileHistory, History, HistoryStore, MovedPickup, ScheduleDiff};
+    use crate::fixtures::{calendar, date, ID};
+    use crate::pickup::{Pickup, PickupType};
+
+    #[test]
+    fn same_schedule_has_no_changes() {
+        let old = calendar(&[(PickupType::Trash, 2), (PickupType::Trash, 9)]);
+        let new = calendar(&[(PickupType::Trash, 9), (PickupType::Trash, 16)]);
+
+        assert!(ScheduleDiff::between(&old.pickups, &new.pickups).is_empty());
+    }
+
+    #[test]
+    fn recycling_week_moved() {
+        let old = calendar(&[
+            (PickupType::Recyclables, 2),
+            (PickupType::Trash, 2),
+            (PickupType::Trash, 9),
+            (PickupType::Recyclables, 16),
+        ]);
+        let new = calendar(&[
+            (PickupType::Trash, 2),
+            (PickupType::Recyclables, 9),
+            (PickupType::Trash, 9),
+        ]);
+
+        let diff = ScheduleDiff::between(&old.pickups, &new.pickups);
+        assert_eq!(
+            diff,
+            ScheduleDiff {
+                added: vec![],
+                removed: vec![],
+                moved: vec![MovedPickup {
+                    name: PickupType::Recyclables,
+                    from: date(2),
+                    to: date(9),
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn pickup_day_changed() {
+        // Monday trash moving to Thursday, with recycling holding the window open
+        let old = calendar(&[
+            (PickupType::Recyclables, 1),
+            (PickupType::Trash, 9),
+            (PickupType::Trash, 16),
+            (PickupType::Trash, 23),
+            (PickupType::Recyclables, 29),
+        ]);
+        let new = calendar(&[
+            (PickupType::Recyclables, 1),
+            (PickupType::Trash, 5),
+            (PickupType::Trash, 12),
+            (PickupType::Trash, 19),
+            (PickupType::Trash, 26),
+            (PickupType::Recyclables, 29),
+        ]);
+
+        let diff = ScheduleDiff::between(&old.pickups, &new.pickups);
+        let moved = |from, to| MovedPickup {
+            name: PickupType::Trash,
+            from: date(from),
+            to: date(to),
+        };
+        assert_eq!(diff.moved, vec![moved(9, 12), moved(16, 19), moved(23, 26)]);
+        assert_eq!(
+            diff.added,
+            vec![Pickup {
+                name: PickupType::Trash,
+                date: date(5)
+            }]
+        );
+        assert!(diff.removed.is_empty());
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let old = calendar(&[(PickupType::Trash, 2), (PickupType::Trash, 16)]);
+        let new = calendar(&[
+            (PickupType::Trash, 2),
+            (PickupType::Organics, 9),
+            (PickupType::Trash, 30),
+        ]);
+
+        let diff = ScheduleDiff::between(&old.pickups, &new.pickups);
+        assert_eq!(
+            diff.added,
+            vec![Pickup {
+                name: PickupType::Organics,
+                date: date(9)
+            }]
+        );
+        assert_eq!(
+            diff.removed,
+            vec![Pickup {
+                name: PickupType::Trash,
+                date: date(16)
+            }]
+        );
+        assert!(diff.moved.is_empty());
+    }
+
+    #[test]
+    fn history_records_changes() {
+        let mut history = History::default();
+
+        assert!(history
+            .record(calendar(&[(PickupType::Trash, 2)]), Utc::now())
+            .is_none());
+        assert!(history
+            .record(calendar(&[(PickupType::Trash, 2)]), Utc::now())
+            .is_none());
+        assert!(history
+            .record(
+                calendar(&[(PickupType::Trash, 2), (PickupType::Organics, 2)]),
+                Utc::now()
+            )
+            .is_some());
+        assert_eq!(history.changes.len(), 1);
+    }
+
+    #[test]
+    fn history_leaves_the_address_out() {
+        let mut history = History::default();
+        history.record(calendar(&[(PickupType::Trash, 2)]), Utc::now());
+
+        let json = serde_json::to_string(&history).unwrap();
+        assert!(!json.contains("AGATE"), "{json}");
+    }
+
+    #[test]
+    fn file_history_round_trip() {
+        let dir = std::env::temp_dir().join(format!("trashcal-history-{}", std::process::id()));
+        let store = FileHistory::new(&dir);
+
+        let mut history = store.load("foo").unwrap();
+        assert_eq!(history, History::default());
+
+        history.record(calendar(&[(PickupType::Trash, 2)]), Utc::now());
+        store.save("foo", &history).unwrap();
+        assert_eq!(store.load("foo").unwrap(), history);
+
+        assert!(store.load("../foo").is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn store_records_changes() {
+        let dir = std::env::temp_dir().join(format!("trashcal-store-{}", std::process::id()));
+        let store = HistoryStore::File(FileHistory::new(&dir));
+
+        store
+            .record(&calendar(&[
+                (PickupType::Trash, 2),
+                (PickupType::Trash, 9),
+                (PickupType::Trash, 16),
+            ]))
+            .await
+            .unwrap();
+        store
+            .record(&calendar(&[
+                (PickupType::Trash, 2),
+                (PickupType::Trash, 10),
+                (PickupType::Trash, 16),
+            ]))
+            .await
+            .unwrap();
+
+        let history = store.load(ID).await.unwrap();
+        assert_eq!(history.changes.len(), 1);
+        assert_eq!(history.changes[0].diff.moved.len(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use reqwest::Client;
+
+use crate::error::Error;
+
+/// How long to wait for the city's server to accept a connection
+pub const CONNECT_TIMEOUT_VAR: &str = "TRASHCAL_CONNECT_TIMEOUT_MS";
+
+/// How long to wait between reads once we're connected
+pub const READ_TIMEOUT_VAR: &str = "TRASHCAL_READ_TIMEOUT_MS";
+
+/// How long the whole request gets, so a hung server can't eat the Lambda timeout
+pub const REQUEST_TIMEOUT_VAR: &str = "TRASHCAL_REQUEST_TIMEOUT_MS";
+
+/// How many idle connections to keep around per host between warm invocations
+pub const POOL_MAX_IDLE_VAR: &str = "TRASHCAL_POOL_MAX_IDLE";
+
+/// Be polite and let the city know who's scraping them
+pub const USER_AGENT: &str = concat!(
+    "trashcal/",
+    env!("CARGO_PKG_VERSION"),
+    " (+https://github.com/stabbylambda/trashcal)"
+);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ClientConfig {
+    pub connect_timeout: Duration,
+    pub read_timeout: Duration,
+    pub request_timeout: Duration,
+    pub pool_max_idle: usize,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            connect_timeout: Duration::from_secs(3),
+            read_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(10),
+            pool_max_idle: 4,
+        }
+    }
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+impl ClientConfig {
+    pub fn from_env() -> ClientConfig {
+        let default = ClientConfig::default();
+        let millis = |name, default: Duration| {
+            Duration::from_millis(env_or(name, default.as_millis() as u64))
+        };
+
+        ClientConfig {
+            connect_timeout: millis(CONNECT_TIMEOUT_VAR, default.connect_timeout),
+            read_timeout: millis(READ_TIMEOUT_VAR, default.read_timeout),
+            request_timeout: millis(REQUEST_TIMEOUT_VAR, default.request_timeout),
+            pool_max_idle: env_or(POOL_MAX_IDLE_VAR, default.pool_max_idle),
+        }
+    }
+
+    pub fn build(&self) -> Result<Client, Error> {
+        let client = Client::builder()
+            .user_agent(USER_AGENT)
+            .connect_timeout(self.connect_timeout)
+            .read_timeout(self.read_timeout)
+            .timeout(self.request_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle)
+            .build()?;
+        Ok(client)
+    }
+}
+
+static CLIENT: LazyLock<Client> = LazyLock::new(|| {
+    ClientConfig::from_env()
+        .build()
+        .expect("Couldn't build the HTTP client")
+});
+
+/// The one HTTP client, shared so warm invocations reuse connections
+pub fn client() -> &'static Client {
+    &CLIENT
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::ClientConfig;
+    use crate::error::Error;
+    use crate::fixtures::silent_server;
+    use crate::retry::{CircuitBreaker, RetryPolicy};
+    use crate::trashcal::fetch_with;
+
+    #[tokio::test]
+    async fn hung_server_times_out() {
+        let url = silent_server().await;
+
+        let client = ClientConfig {
+            request_timeout: Duration::from_millis(200),
+            ..ClientConfig::default()
+        }
+        .build()
+        .unwrap();
+
+        let policy = RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        };
+        let result = fetch_with(&client, &url, &policy, &CircuitBreaker::default()).await;
+        assert!(matches!(result, Err(Error::Timeout)), "{result:?}");
+    }
+}
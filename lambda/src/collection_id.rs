@@ -0,0 +1,153 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use url::Url;
+
+use crate::error::Error;
+use crate::format::OutputFormat;
+
+/// Where the city keeps the page for each collection ID
+pub const UPSTREAM_URL: &str = "https://getitdone.sandiego.gov/CollectionDetail";
+
+/// As far as I can tell, all collection IDs start with this (it's the Salesforce object's key prefix)
+const KEY_PREFIX: &str = "a4O";
+
+const CHECKSUM_CHARS: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ012345";
+
+/// A Salesforce collection ID, always in its case-insensitive 18 character form
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct CollectionId(String);
+
+/// The three characters Salesforce appends to a 15 character ID: one per five character chunk,
+/// with a bit set for every uppercase letter in the chunk
+fn checksum(id: &str) -> String {
+    id.as_bytes()
+        .chunks(5)
+        .map(|chunk| {
+            let bits = chunk
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| c.is_ascii_uppercase())
+                .fold(0, |bits, (i, _)| bits | (1 << i));
+            CHECKSUM_CHARS[bits] as char
+        })
+        .collect()
+}
+
+impl CollectionId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The city's page for this ID
+    pub fn upstream_url(&self) -> Url {
+        self.upstream_url_at(UPSTREAM_URL)
+            .expect("the upstream URL is always valid")
+    }
+
+    /// This ID's page on a copy of the city's site at `base`
+    pub fn upstream_url_at(&self, base: &str) -> Result<Url, url::ParseError> {
+        Url::parse_with_params(base, [("id", self.as_str())])
+    }
+}
+
+impl FromStr for CollectionId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::IdError(s.to_string());
+
+        // only a single, known, trailing extension comes off
+        let id = match s.rsplit_once('.') {
+            Some((id, extension)) if extension.parse::<OutputFormat>().is_ok() => id,
+            _ => s,
+        };
+
+        if !id.starts_with(KEY_PREFIX) || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(invalid());
+        }
+
+        match id.len() {
+            15 => Ok(CollectionId(format!("{id}{}", checksum(id)))),
+            18 => {
+                let (base, suffix) = id.split_at(15);
+                if checksum(base).eq_ignore_ascii_case(suffix) {
+                    Ok(CollectionId(format!(
+                        "{base}{}",
+                        suffix.to_ascii_uppercase()
+                    )))
+                } else {
+                    Err(invalid())
+                }
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Display for CollectionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CollectionId;
+
+    #[test]
+    fn valid_18_character_id() {
+        let id: CollectionId = "a4Ot0000001E8i4EAC".parse().unwrap();
+        assert_eq!(id.as_str(), "a4Ot0000001E8i4EAC");
+    }
+
+    #[test]
+    fn checksum_is_case_insensitive() {
+        let id: CollectionId = "a4Ot0000001E8i4eac".parse().unwrap();
+        assert_eq!(id.as_str(), "a4Ot0000001E8i4EAC");
+    }
+
+    #[test]
+    fn converts_15_to_18() {
+        let id: CollectionId = "a4Ot0000001E8i4".parse().unwrap();
+        assert_eq!(id.as_str(), "a4Ot0000001E8i4EAC");
+    }
+
+    #[test]
+    fn bad_checksum() {
+        assert!("a4Ot0000001E8i4AAA".parse::<CollectionId>().is_err());
+    }
+
+    #[test]
+    fn strips_only_a_trailing_known_extension() {
+        let id: CollectionId = "a4Ot0000001E8i4EAC.json".parse().unwrap();
+        assert_eq!(id.as_str(), "a4Ot0000001E8i4EAC");
+
+        assert!("a4Ot0000001E8i4EAC.xml".parse::<CollectionId>().is_err());
+        assert!("a4Ot0000001E8i4EAC.json.foo"
+            .parse::<CollectionId>()
+            .is_err());
+        assert!("a4O.json.foo".parse::<CollectionId>().is_err());
+        assert!("a4Ot0000001E8i4EAC.ics.json"
+            .parse::<CollectionId>()
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!("null".parse::<CollectionId>().is_err());
+        assert!("a4OtZZZZZZZZ".parse::<CollectionId>().is_err());
+        assert!("a4Ot0000001E8i4&x=".parse::<CollectionId>().is_err());
+        assert!("a4Ot0000001E8i4EAC?x=1".parse::<CollectionId>().is_err());
+        assert!("".parse::<CollectionId>().is_err());
+    }
+
+    #[test]
+    fn upstream_url() {
+        let id: CollectionId = "a4Ot0000001E8i4EAC".parse().unwrap();
+        assert_eq!(
+            id.upstream_url().as_str(),
+            "https://getitdone.sandiego.gov/CollectionDetail?id=a4Ot0000001E8i4EAC"
+        );
+    }
+}
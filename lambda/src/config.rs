@@ -0,0 +1,18 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+
+/// Emoji are on unless a subscriber says otherwise
+pub fn default_whimsy() -> bool {
+    true
+}
+
+/// Reads a JSON array (e.g. the webhook or digest subscriptions) from `var`. Nobody's
+/// subscribed when it isn't set.
+pub fn list_from_env<T: DeserializeOwned>(var: &str) -> Result<Vec<T>> {
+    match std::env::var(var) {
+        Ok(config) => {
+            serde_json::from_str(&config).with_context(|| format!("Couldn't parse {var}"))
+        }
+        Err(_) => Ok(vec![]),
+    }
+}
@@ -0,0 +1,183 @@
+use http::header::{
+    ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+    ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, CACHE_CONTROL, ORIGIN, VARY,
+};
+use http::{HeaderMap, HeaderValue, StatusCode};
+use lambda_http::{Body, Request, Response};
+
+/// Comma separated origins that may read trashcal from a browser, or `*` for anyone
+pub const ALLOWED_ORIGINS_VAR: &str = "TRASHCAL_ALLOWED_ORIGINS";
+
+/// Everything we answer to
+pub const ALLOWED_METHODS: &str = "GET, HEAD, OPTIONS";
+
+/// How long browsers can skip the preflight, in seconds
+const MAX_AGE: &str = "86400";
+
+/// Which browser origins get to read our responses
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AllowedOrigins {
+    Any,
+    Only(Vec<String>),
+}
+
+impl Default for AllowedOrigins {
+    /// Calendars are public and nobody sends credentials, so anyone can have them
+    fn default() -> Self {
+        AllowedOrigins::Any
+    }
+}
+
+impl AllowedOrigins {
+    pub fn from_env() -> AllowedOrigins {
+        std::env::var(ALLOWED_ORIGINS_VAR)
+            .ok()
+            .map(|v| AllowedOrigins::parse(&v))
+            .unwrap_or_default()
+    }
+
+    pub fn parse(value: &str) -> AllowedOrigins {
+        if value.trim() == "*" {
+            return AllowedOrigins::Any;
+        }
+
+        let origins = value
+            .split(',')
+            .map(|o| o.trim().trim_end_matches('/').to_string())
+            .filter(|o| !o.is_empty())
+            .collect();
+        AllowedOrigins::Only(origins)
+    }
+
+    /// The `Access-Control-Allow-Origin` value for a request from `origin`, if it's allowed
+    pub fn allow(&self, origin: Option<&str>) -> Option<String> {
+        match self {
+            AllowedOrigins::Any => Some("*".to_string()),
+            AllowedOrigins::Only(origins) => origin
+                .filter(|origin| origins.iter().any(|o| o == origin))
+                .map(str::to_string),
+        }
+    }
+
+    /// Adds the CORS headers to a response for `request`
+    pub fn apply(&self, request: &Request, response: &mut Response<Body>) {
+        add_headers(self, request.headers(), response.headers_mut());
+    }
+
+    /// The answer to a preflight `OPTIONS` request. A disallowed origin still gets a 204, just
+    /// without the headers that would let the browser go ahead.
+    pub fn preflight(&self, request: &Request) -> http::Result<Response<Body>> {
+        let mut response = Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(Body::Empty)?;
+
+        let headers = response.headers_mut();
+        add_headers(self, request.headers(), headers);
+        if headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN) {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_METHODS,
+                HeaderValue::from_static(ALLOWED_METHODS),
+            );
+            headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from_static(MAX_AGE));
+            if let Some(requested) = request.headers().get(ACCESS_CONTROL_REQUEST_HEADERS) {
+                headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, requested.clone());
+            }
+        }
+        Ok(response)
+    }
+}
+
+fn add_headers(allowed: &AllowedOrigins, request: &HeaderMap, response: &mut HeaderMap) {
+    let origin = request.get(ORIGIN).and_then(|o| o.to_str().ok());
+    if let Some(value) = allowed
+        .allow(origin)
+        .and_then(|v| HeaderValue::from_str(&v).ok())
+    {
+        response.insert(ACCESS_CONTROL_ALLOW_ORIGIN, value);
+    }
+
+    // an echoed origin is only right for that origin, so caches have to keep them apart.
+    // CloudFront's cache key leaves Origin out, so it mustn't keep them at all.
+    if matches!(allowed, AllowedOrigins::Only(_)) {
+        response.append(VARY, HeaderValue::from_static("Origin"));
+        let cache_control = match response.get(CACHE_CONTROL).and_then(|v| v.to_str().ok()) {
+            Some(v) if v.contains("no-store") || v.contains("private") => None,
+            Some(v) => HeaderValue::from_str(&format!("private, {v}")).ok(),
+            None => Some(HeaderValue::from_static("private")),
+        };
+        if let Some(value) = cache_control {
+            response.insert(CACHE_CONTROL, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http::header::{CACHE_CONTROL, ORIGIN};
+    use http::{HeaderMap, HeaderValue};
+
+    use super::{add_headers, AllowedOrigins};
+
+    #[test]
+    fn anyone() {
+        let allowed = AllowedOrigins::parse("*");
+        assert_eq!(allowed, AllowedOrigins::Any);
+        assert_eq!(allowed.allow(None).as_deref(), Some("*"));
+        assert_eq!(
+            allowed.allow(Some("https://example.com")).as_deref(),
+            Some("*")
+        );
+    }
+
+    #[test]
+    fn only_listed_origins() {
+        let allowed = AllowedOrigins::parse("https://example.com/, https://trash.example.org");
+        assert_eq!(
+            allowed.allow(Some("https://example.com")).as_deref(),
+            Some("https://example.com")
+        );
+        assert_eq!(
+            allowed.allow(Some("https://trash.example.org")).as_deref(),
+            Some("https://trash.example.org")
+        );
+        assert_eq!(allowed.allow(Some("https://evil.example.net")), None);
+        assert_eq!(allowed.allow(None), None);
+    }
+
+    #[test]
+    fn empty_allows_nobody() {
+        let allowed = AllowedOrigins::parse("");
+        assert_eq!(allowed, AllowedOrigins::Only(vec![]));
+        assert_eq!(allowed.allow(Some("https://example.com")), None);
+    }
+
+    #[test]
+    fn echoed_origins_stay_out_of_shared_caches() {
+        let mut request = HeaderMap::new();
+        request.insert(ORIGIN, HeaderValue::from_static("https://example.com"));
+        let cache_control = |allowed: &AllowedOrigins, existing: Option<&'static str>| {
+            let mut response = HeaderMap::new();
+            if let Some(existing) = existing {
+                response.insert(CACHE_CONTROL, HeaderValue::from_static(existing));
+            }
+            add_headers(allowed, &request, &mut response);
+            response
+                .get(CACHE_CONTROL)
+                .map(|v| v.to_str().unwrap().to_string())
+        };
+
+        let only = AllowedOrigins::parse("https://example.com");
+        assert_eq!(cache_control(&only, None).as_deref(), Some("private"));
+        assert_eq!(
+            cache_control(&only, Some("max-age=60")).as_deref(),
+            Some("private, max-age=60")
+        );
+        assert_eq!(
+            cache_control(&only, Some("no-store")).as_deref(),
+            Some("no-store")
+        );
+
+        // `*` is the same for everybody, so shared caches are fine
+        assert_eq!(cache_control(&AllowedOrigins::Any, None), None);
+    }
+}
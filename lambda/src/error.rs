@@ -1,14 +1,41 @@
+use http::StatusCode;
+use strum::IntoStaticStr;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+use crate::redact;
+
+#[derive(Error, Debug, IntoStaticStr)]
 
 pub enum Error {
-    #[error("Got an ID that made no sense: {0}")]
+    // these end up in the lambda's own logs, so the ID goes through redaction like everywhere else
+    #[error("Got an ID that made no sense: {}", redact::id(.0))]
     IdError(String),
-    #[error("The city has no idea what this ID is: {0}")]
+    #[error("No such format: .{0}")]
+    UnknownExtension(String),
+    #[error("No such page: {0}")]
+    UnknownPath(String),
+    #[error("Can't produce the format {0}")]
+    UnknownFormat(String),
+    #[error("Bad value for {name}: {value}")]
+    BadOption { name: String, value: String },
+    #[error("The city has no idea what this ID is: {}", redact::id(.0))]
     RedirectPage(String),
-    #[error("HTTP Error")]
-    HttpError(#[from] reqwest::Error),
+    #[error("The city is down for maintenance")]
+    MaintenancePage,
+    #[error("The city wants us to log in")]
+    LoginWall,
+    #[error("The city sent a page we don't recognize")]
+    UnrecognizedPage,
+    #[error("HTTP Error: {0}")]
+    HttpError(reqwest::Error),
+    #[error("Timed out talking to the city")]
+    Timeout,
+    #[error("Giving the city a break after too many failures")]
+    CircuitOpen,
+    #[error("The city's server answered {0}")]
+    UpstreamUnavailable(StatusCode),
+    #[error("The city is rate limiting us")]
+    RateLimited { retry_after: Option<u64> },
     #[error("Parse Error")]
     ParseError,
     #[error("Enum Parse Error")]
@@ -16,3 +43,106 @@ pub enum Error {
     #[error("DateTime Error")]
     TimeZoneError(#[from] chrono::format::ParseError),
 }
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Error::Timeout
+        } else {
+            // reqwest puts the URL in its message, and our URLs all have a collection ID in them
+            Error::HttpError(if redact::enabled() {
+                e.without_url()
+            } else {
+                e
+            })
+        }
+    }
+}
+
+impl Error {
+    /// What to tell our own callers when the city lets us down, if it's the city's fault
+    pub fn upstream_status(&self) -> Option<StatusCode> {
+        match self {
+            Error::UpstreamUnavailable(_) | Error::LoginWall | Error::UnrecognizedPage => {
+                Some(StatusCode::BAD_GATEWAY)
+            }
+            Error::RateLimited { .. } | Error::CircuitOpen | Error::MaintenancePage => {
+                Some(StatusCode::SERVICE_UNAVAILABLE)
+            }
+            Error::Timeout => Some(StatusCode::GATEWAY_TIMEOUT),
+            _ => None,
+        }
+    }
+
+    /// What to tell callers when they asked for something we can't give them
+    pub fn client_status(&self) -> Option<StatusCode> {
+        match self {
+            Error::BadOption { .. } => Some(StatusCode::BAD_REQUEST),
+            Error::UnknownExtension(_) | Error::UnknownPath(_) => Some(StatusCode::NOT_FOUND),
+            Error::UnknownFormat(_) => Some(StatusCode::NOT_ACCEPTABLE),
+            _ => None,
+        }
+    }
+
+    /// The variant name, for counting errors by kind
+    pub fn kind(&self) -> &'static str {
+        self.into()
+    }
+
+    /// Seconds callers should wait before trying again
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            Error::RateLimited { retry_after } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http::StatusCode;
+
+    use super::Error;
+
+    #[test]
+    fn upstream_statuses() {
+        assert_eq!(
+            Error::UpstreamUnavailable(StatusCode::INTERNAL_SERVER_ERROR).upstream_status(),
+            Some(StatusCode::BAD_GATEWAY)
+        );
+        assert_eq!(
+            Error::RateLimited {
+                retry_after: Some(30)
+            }
+            .upstream_status(),
+            Some(StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert_eq!(Error::ParseError.upstream_status(), None);
+    }
+
+    #[test]
+    fn kind() {
+        assert_eq!(Error::Timeout.kind(), "Timeout");
+        assert_eq!(
+            Error::RateLimited { retry_after: None }.kind(),
+            "RateLimited"
+        );
+    }
+
+    #[test]
+    fn client_statuses() {
+        assert_eq!(
+            Error::UnknownExtension("xml".to_string()).client_status(),
+            Some(StatusCode::NOT_FOUND)
+        );
+        assert_eq!(
+            Error::UnknownFormat("pdf".to_string()).client_status(),
+            Some(StatusCode::NOT_ACCEPTABLE)
+        );
+        assert_eq!(
+            Error::UnknownFormat("pdf".to_string()).upstream_status(),
+            None
+        );
+        assert_eq!(Error::Timeout.client_status(), None);
+    }
+}
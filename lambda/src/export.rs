@@ -0,0 +1,143 @@
+use anyhow::Result;
+use itertools::Itertools;
+
+use crate::base_url::BaseUrl;
+use crate::options::RenderOptions;
+use crate::pickup_calendar::PickupCalendar;
+use crate::render::Renderer;
+
+/// Quote a CSV field if it has anything that would confuse a spreadsheet (RFC 4180)
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl PickupCalendar {
+    /// One row per pickup, for spreadsheets
+    pub fn to_csv(&self, options: &RenderOptions) -> String {
+        let header = "date,weekday,type,address\r\n";
+        let rows = self
+            .pickups
+            .iter()
+            .map(|p| {
+                [
+                    p.date.to_string(),
+                    p.date.format("%A").to_string(),
+                    options.pickup_name(p.name),
+                    self.address.clone(),
+                ]
+                .iter()
+                .map(|f| csv_field(f))
+                .join(",")
+            })
+            .map(|row| format!("{row}\r\n"))
+            .join("");
+
+        format!("{header}{rows}")
+    }
+
+    /// One line per pickup date, for humans and grep
+    pub fn to_text(&self, options: &RenderOptions) -> String {
+        let pickups = self
+            .pickups
+            .iter()
+            .chunk_by(|p| p.date)
+            .into_iter()
+            .map(|(date, pickups)| {
+                let names = pickups.map(|p| options.pickup_name(p.name)).join(", ");
+                format!("{date} {}: {names}\n", date.format("%A"))
+            })
+            .join("");
+
+        format!("{}\n\n{pickups}", self.address)
+    }
+}
+
+pub struct CsvRenderer;
+
+impl Renderer for CsvRenderer {
+    fn content_type(&self) -> &'static str {
+        "text/csv;charset=UTF-8"
+    }
+
+    fn filename(&self) -> Option<&'static str> {
+        Some("trashcal.csv")
+    }
+
+    fn render(
+        &self,
+        calendar: &PickupCalendar,
+        _base_url: &BaseUrl,
+        options: &RenderOptions,
+    ) -> Result<String> {
+        Ok(calendar.to_csv(options))
+    }
+}
+
+pub struct TextRenderer;
+
+impl Renderer for TextRenderer {
+    fn content_type(&self) -> &'static str {
+        "text/plain;charset=UTF-8"
+    }
+
+    fn filename(&self) -> Option<&'static str> {
+        Some("trashcal.txt")
+    }
+
+    fn inline(&self) -> bool {
+        true
+    }
+
+    fn render(
+        &self,
+        calendar: &PickupCalendar,
+        _base_url: &BaseUrl,
+        options: &RenderOptions,
+    ) -> Result<String> {
+        Ok(calendar.to_text(options))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures;
+    use crate::options::RenderOptions;
+    use crate::pickup::PickupType;
+    use crate::pickup_calendar::PickupCalendar;
+
+    fn calendar() -> PickupCalendar {
+        fixtures::calendar_at(
+            "1234 \"AGATE\" ST, San Diego, CA 92101",
+            &[(PickupType::Recyclables, 2), (PickupType::Trash, 2)],
+        )
+    }
+
+    #[test]
+    fn csv_quotes_address() {
+        assert_eq!(
+            calendar().to_csv(&RenderOptions::with_whimsy(false)),
+            "date,weekday,type,address\r\n\
+            2023-01-02,Monday,Recyclables,\"1234 \"\"AGATE\"\" ST, San Diego, CA 92101\"\r\n\
+            2023-01-02,Monday,Trash,\"1234 \"\"AGATE\"\" ST, San Diego, CA 92101\"\r\n"
+        );
+    }
+
+    #[test]
+    fn csv_with_whimsy() {
+        assert!(calendar()
+            .to_csv(&RenderOptions::with_whimsy(true))
+            .contains(",♻️ Recyclables,"));
+    }
+
+    #[test]
+    fn text_groups_by_date() {
+        assert_eq!(
+            calendar().to_text(&RenderOptions::with_whimsy(false)),
+            "1234 \"AGATE\" ST, San Diego, CA 92101\n\n2023-01-02 Monday: Recyclables, Trash\n"
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use anyhow::Result;
+use chrono::{DateTime, Days, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use itertools::Itertools;
+
+use crate::base_url::BaseUrl;
+use crate::html::escape;
+use crate::options::RenderOptions;
+use crate::pickup_calendar::PickupCalendar;
+use crate::render::Renderer;
+
+/// One feed entry per pickup date
+struct FeedEntry {
+    id: String,
+    link: String,
+    title: String,
+    updated: DateTime<Utc>,
+}
+
+/// Entries are "published" at 6PM local time the night before, which is when the bins go out.
+/// Deriving it from the date keeps it stable no matter how often the feed is fetched.
+fn announced_at(date: NaiveDate, timezone: Tz) -> DateTime<Utc> {
+    date.checked_sub_days(Days::new(1))
+        .and_then(|d| d.and_hms_opt(18, 0, 0))
+        .and_then(|d| timezone.from_local_datetime(&d).single())
+        .map(|d| d.to_utc())
+        .unwrap_or_else(|| date.and_time(Default::default()).and_utc())
+}
+
+/// The feed's own URL, options and all, so readers keep getting what they subscribed to
+fn self_link(url: &str, extension: &str, options: &RenderOptions) -> String {
+    match options.to_query() {
+        query if query.is_empty() => format!("{url}.{extension}"),
+        query => format!("{url}.{extension}?{query}"),
+    }
+}
+
+impl PickupCalendar {
+    /// Entries for the pickups that have been announced by `now`. Later ones show up once
+    /// their bins are due to go out, so readers don't see a week of pickups all at once.
+    fn feed_entries(
+        &self,
+        base_url: &BaseUrl,
+        options: &RenderOptions,
+        now: DateTime<Utc>,
+    ) -> Vec<FeedEntry> {
+        let url = base_url.calendar_url(&self.id);
+
+        self.pickups
+            .iter()
+            .chunk_by(|p| p.date)
+            .into_iter()
+            .map(|(date, pickups)| {
+                let names = pickups.map(|p| options.pickup_name(p.name)).join(", ");
+
+                FeedEntry {
+                    // the date never changes for a given pickup, so neither does the ID
+                    id: format!("{url}#{date}"),
+                    link: format!("{url}.html"),
+                    title: format!("{}: {names}", date.format("%A, %B %-d")),
+                    updated: announced_at(date, options.timezone),
+                }
+            })
+            .filter(|e| e.updated <= now)
+            .collect()
+    }
+
+    /// The next announcement, since the feed changes then, or the calendar's own expiry if
+    /// that's sooner
+    fn feed_valid_until(
+        &self,
+        options: &RenderOptions,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let next_announcement = self
+            .pickups
+            .iter()
+            .map(|p| announced_at(p.date, options.timezone))
+            .filter(|announced| *announced > now)
+            .min();
+        [next_announcement, self.valid_until()]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// The latest entry, or `now` when nothing's been announced yet
+    fn feed_updated(entries: &[FeedEntry], now: DateTime<Utc>) -> DateTime<Utc> {
+        entries.iter().map(|e| e.updated).max().unwrap_or(now)
+    }
+
+    pub fn to_atom(
+        &self,
+        base_url: &BaseUrl,
+        options: &RenderOptions,
+        now: DateTime<Utc>,
+    ) -> String {
+        let url = base_url.calendar_url(&self.id);
+        let entries = self.feed_entries(base_url, options, now);
+        let updated = Self::feed_updated(&entries, now).to_rfc3339();
+
+        let entries = entries
+            .iter()
+            .map(|e| {
+                format!(
+                    "<entry>
+<id>{id}</id>
+<title>{title}</title>
+<link href=\"{link}\"/>
+<updated>{updated}</updated>
+<summary>{title}</summary>
+</entry>
+",
+                    id = escape(&e.id),
+                    title = escape(&e.title),
+                    link = escape(&e.link),
+                    updated = e.updated.to_rfc3339(),
+                )
+            })
+            .join("");
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<feed xmlns=\"http://www.w3.org/2005/Atom\">
+<id>{id}</id>
+<title>Trashcal: {address}</title>
+<link href=\"{link}\"/>
+<link rel=\"self\" href=\"{self_link}\"/>
+<updated>{updated}</updated>
+<author><name>Trashcal</name></author>
+{entries}</feed>
+",
+            id = escape(&url),
+            link = escape(&format!("{url}.html")),
+            self_link = escape(&self_link(&url, "atom", options)),
+            address = escape(&self.address),
+        )
+    }
+
+    pub fn to_rss(
+        &self,
+        base_url: &BaseUrl,
+        options: &RenderOptions,
+        now: DateTime<Utc>,
+    ) -> String {
+        let url = base_url.calendar_url(&self.id);
+        let entries = self.feed_entries(base_url, options, now);
+        let updated = Self::feed_updated(&entries, now).to_rfc2822();
+
+        let items = entries
+            .iter()
+            .map(|e| {
+                format!(
+                    "<item>
+<title>{title}</title>
+<link>{link}</link>
+<guid isPermaLink=\"false\">{id}</guid>
+<pubDate>{updated}</pubDate>
+</item>
+",
+                    id = escape(&e.id),
+                    title = escape(&e.title),
+                    link = escape(&e.link),
+                    updated = e.updated.to_rfc2822(),
+                )
+            })
+            .join("");
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<rss version=\"2.0\">
+<channel>
+<title>Trashcal: {address}</title>
+<link>{link}</link>
+<description>Trash pickups for {address}</description>
+<lastBuildDate>{updated}</lastBuildDate>
+{items}</channel>
+</rss>
+",
+            link = escape(&format!("{url}.html")),
+            address = escape(&self.address),
+        )
+    }
+}
+
+pub struct AtomRenderer;
+
+impl Renderer for AtomRenderer {
+    fn content_type(&self) -> &'static str {
+        "application/atom+xml;charset=UTF-8"
+    }
+
+    fn render(
+        &self,
+        calendar: &PickupCalendar,
+        base_url: &BaseUrl,
+        options: &RenderOptions,
+    ) -> Result<String> {
+        Ok(calendar.to_atom(base_url, options, Utc::now()))
+    }
+
+    fn valid_until(
+        &self,
+        calendar: &PickupCalendar,
+        options: &RenderOptions,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        calendar.feed_valid_until(options, now)
+    }
+}
+
+pub struct RssRenderer;
+
+impl Renderer for RssRenderer {
+    fn content_type(&self) -> &'static str {
+        "application/rss+xml;charset=UTF-8"
+    }
+
+    fn render(
+        &self,
+        calendar: &PickupCalendar,
+        base_url: &BaseUrl,
+        options: &RenderOptions,
+    ) -> Result<String> {
+        Ok(calendar.to_rss(base_url, options, Utc::now()))
+    }
+
+    fn valid_until(
+        &self,
+        calendar: &PickupCalendar,
+        options: &RenderOptions,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        calendar.feed_valid_until(options, now)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, Utc};
+
+    use crate::base_url::BaseUrl;
+    use crate::fixtures::{calendar, calendar_at, TWO_WEEKS};
+    use crate::options::RenderOptions;
+    use crate::render::Renderer;
+
+    use super::{AtomRenderer, RssRenderer};
+
+    /// Long after every pickup
+    fn later() -> DateTime<Utc> {
+        "2023-02-01T00:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn atom_has_one_entry_per_date() {
+        let atom = calendar_at("1234 AGATE ST & SONS", TWO_WEEKS).to_atom(
+            &BaseUrl::default(),
+            &RenderOptions::with_whimsy(false),
+            later(),
+        );
+
+        assert_eq!(atom.matches("<entry>").count(), 2);
+        assert!(atom
+            .contains("<id>https://stabbylambda.com/trashcal/a4Ot0000001E8i4EAC#2023-01-09</id>"));
+        assert!(atom.contains("<title>Monday, January 9: Recyclables, Trash</title>"));
+        assert!(atom.contains("<updated>2023-01-09T02:00:00+00:00</updated>"));
+        assert!(atom.contains("1234 AGATE ST &amp; SONS"));
+        assert!(atom.contains(
+            "<link rel=\"self\" href=\"https://stabbylambda.com/trashcal/a4Ot0000001E8i4EAC.atom?whimsy=false\"/>"
+        ));
+    }
+
+    #[test]
+    fn only_announced_pickups() {
+        // after the first pickup went out, but before the second was announced
+        let now = "2023-01-05T00:00:00Z".parse().unwrap();
+        let atom = calendar(TWO_WEEKS).to_atom(&BaseUrl::default(), &RenderOptions::default(), now);
+
+        assert_eq!(atom.matches("<entry>").count(), 1);
+        assert!(!atom.contains("2023-01-09"));
+        assert!(atom.contains("<updated>2023-01-02T02:00:00+00:00</updated>\n<author>"));
+        assert!(atom
+            .contains("<link rel=\"self\" href=\"https://stabbylambda.com/trashcal/a4Ot0000001E8i4EAC.atom\"/>"));
+
+        // before anything was announced
+        let now = "2022-12-25T00:00:00Z".parse().unwrap();
+        let rss = calendar(TWO_WEEKS).to_rss(&BaseUrl::default(), &RenderOptions::default(), now);
+        assert_eq!(rss.matches("<item>").count(), 0);
+        assert!(rss.contains("<lastBuildDate>Sun, 25 Dec 2022 00:00:00 +0000</lastBuildDate>"));
+    }
+
+    #[test]
+    fn feeds_expire_at_the_next_announcement() {
+        let options = RenderOptions::default();
+
+        // fetched before the first pickup was announced, so it's stale once it is
+        let now = "2022-12-25T00:00:00Z".parse().unwrap();
+        let announced: DateTime<Utc> = "2023-01-02T02:00:00Z".parse().unwrap();
+        assert_eq!(
+            AtomRenderer.valid_until(&calendar(TWO_WEEKS), &options, now),
+            Some(announced)
+        );
+        assert_eq!(
+            RssRenderer.valid_until(&calendar(TWO_WEEKS), &options, now),
+            Some(announced)
+        );
+
+        // the next announcement is after the calendar goes stale anyway
+        let now = "2023-01-02T03:00:00Z".parse().unwrap();
+        assert_eq!(
+            AtomRenderer.valid_until(&calendar(TWO_WEEKS), &options, now),
+            calendar(TWO_WEEKS).valid_until()
+        );
+    }
+
+    #[test]
+    fn rss_has_one_item_per_date() {
+        let rss = calendar(TWO_WEEKS).to_rss(
+            &BaseUrl::default(),
+            &RenderOptions::with_whimsy(true),
+            later(),
+        );
+
+        assert_eq!(rss.matches("<item>").count(), 2);
+        assert!(rss.contains(
+            "<guid isPermaLink=\"false\">https://stabbylambda.com/trashcal/a4Ot0000001E8i4EAC#2023-01-02</guid>"
+        ));
+        assert!(rss.contains("<pubDate>Mon, 2 Jan 2023 02:00:00 +0000</pubDate>"));
+        assert!(rss.contains("<title>Monday, January 2: 🗑️ Trash</title>"));
+    }
+}
@@ -0,0 +1,90 @@
+//! Calendars and fake servers for the unit tests, so each module doesn't grow its own copy
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::pickup::{Pickup, PickupType};
+use crate::pickup_calendar::PickupCalendar;
+
+/// A day in January 2023, which starts on a Sunday
+pub fn date(d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2023, 1, d).unwrap()
+}
+
+pub fn pickup(name: PickupType, d: u32) -> Pickup {
+    Pickup {
+        name,
+        date: date(d),
+    }
+}
+
+/// The README's collection ID, for 1234 Agate St
+pub const ID: &str = "a4Ot0000001E8i4EAC";
+
+/// Trash on Monday the 2nd, then recycling and trash on the 9th
+pub const TWO_WEEKS: &[(PickupType, u32)] = &[
+    (PickupType::Trash, 2),
+    (PickupType::Recyclables, 9),
+    (PickupType::Trash, 9),
+];
+
+/// A calendar for 1234 Agate St with these pickups, in the order given
+pub fn calendar(pickups: &[(PickupType, u32)]) -> PickupCalendar {
+    calendar_at("1234 AGATE ST", pickups)
+}
+
+/// A calendar for somewhere else, usually an address that needs escaping
+pub fn calendar_at(address: &str, pickups: &[(PickupType, u32)]) -> PickupCalendar {
+    PickupCalendar {
+        id: ID.to_string(),
+        address: address.to_string(),
+        pickups: pickups.iter().map(|(name, d)| pickup(*name, *d)).collect(),
+    }
+}
+
+/// A whole HTTP response with `status` and `body`
+pub fn response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Answers the nth request (counting from 0) with `respond(n)`, along with how many requests
+/// it's had
+pub async fn server(respond: impl Fn(u32) -> String + Send + 'static) -> (String, Arc<AtomicU32>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicU32::new(0));
+
+    let count = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let _ = socket.read(&mut buf).await.unwrap();
+
+            let n = count.fetch_add(1, Ordering::SeqCst);
+            socket.write_all(respond(n).as_bytes()).await.unwrap();
+        }
+    });
+
+    (url, requests)
+}
+
+/// Accepts connections and then never says anything
+pub async fn silent_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let mut sockets = vec![];
+        loop {
+            sockets.push(listener.accept().await.unwrap());
+        }
+    });
+    url
+}
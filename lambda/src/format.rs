@@ -0,0 +1,94 @@
+use strum::{Display, EnumIter, EnumString};
+
+use crate::error::Error;
+
+/// Every way we know how to hand out a calendar, named by its URL suffix
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "lowercase")]
+pub enum OutputFormat {
+    #[strum(to_string = "ics")]
+    ICal,
+    Json,
+    Html,
+    Csv,
+    #[strum(to_string = "txt")]
+    Text,
+    Atom,
+    Rss,
+}
+
+impl OutputFormat {
+    /// The suffix that picks this format, without the dot
+    pub fn extension(self) -> String {
+        self.to_string()
+    }
+
+    /// Splits `{id}.{extension}` into the ID and the format it asks for. Collection IDs are
+    /// alphanumeric, so everything after the first dot is the suffix, and a suffix we don't
+    /// know is a page that doesn't exist rather than a quiet fallback to iCal.
+    pub fn split(path: &str) -> Result<(&str, OutputFormat), Error> {
+        match path.split_once('.') {
+            Some((id, extension)) => extension
+                .parse()
+                .map(|format| (id, format))
+                .map_err(|_| Error::UnknownExtension(extension.to_string())),
+            None => Ok((path, OutputFormat::ICal)),
+        }
+    }
+
+    /// The value of a `?format=` override, which has to be a format we can actually produce
+    pub fn from_query(value: &str) -> Result<OutputFormat, Error> {
+        value
+            .parse()
+            .map_err(|_| Error::UnknownFormat(value.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use strum::IntoEnumIterator;
+
+    use super::OutputFormat;
+    use crate::error::Error;
+
+    #[test]
+    fn no_suffix_is_ical() {
+        assert_eq!(
+            OutputFormat::split("a4Ot0000001E8i4EAC").unwrap(),
+            ("a4Ot0000001E8i4EAC", OutputFormat::ICal)
+        );
+    }
+
+    #[test]
+    fn every_format_round_trips_through_its_suffix() {
+        for format in OutputFormat::iter() {
+            let path = format!("a4Ot0000001E8i4EAC.{}", format.extension());
+            assert_eq!(
+                OutputFormat::split(&path).unwrap(),
+                ("a4Ot0000001E8i4EAC", format)
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_suffixes_are_errors() {
+        assert!(matches!(
+            OutputFormat::split("a4Ot0000001E8i4EAC.xml"),
+            Err(Error::UnknownExtension(ext)) if ext == "xml"
+        ));
+        assert!(matches!(
+            OutputFormat::split("a4Ot0000001E8i4EAC.json.foo"),
+            Err(Error::UnknownExtension(ext)) if ext == "json.foo"
+        ));
+        assert!(OutputFormat::split("a4Ot0000001E8i4EAC.").is_err());
+    }
+
+    #[test]
+    fn query_override() {
+        assert_eq!(OutputFormat::from_query("csv").unwrap(), OutputFormat::Csv);
+        assert!(matches!(
+            OutputFormat::from_query("pdf"),
+            Err(Error::UnknownFormat(f)) if f == "pdf"
+        ));
+    }
+}
@@ -0,0 +1,61 @@
+/// Where on the map a pickup happens, to the microdegree (about 10cm), which is plenty for a
+/// curb. Whole microdegrees keep it `Eq` and give every spot exactly one way to be written.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Coordinates {
+    lat: i32,
+    lon: i32,
+}
+
+fn microdegrees(degrees: f64, max: f64) -> Option<i32> {
+    (degrees.is_finite() && degrees.abs() <= max).then(|| (degrees * 1e6).round() as i32)
+}
+
+impl Coordinates {
+    pub fn new(latitude: f64, longitude: f64) -> Option<Coordinates> {
+        Some(Coordinates {
+            lat: microdegrees(latitude, 90.0)?,
+            lon: microdegrees(longitude, 180.0)?,
+        })
+    }
+
+    pub fn parse(latitude: &str, longitude: &str) -> Option<Coordinates> {
+        Coordinates::new(
+            latitude.trim().parse().ok()?,
+            longitude.trim().parse().ok()?,
+        )
+    }
+
+    pub fn latitude(&self) -> f64 {
+        f64::from(self.lat) / 1e6
+    }
+
+    pub fn longitude(&self) -> f64 {
+        f64::from(self.lon) / 1e6
+    }
+
+    /// The iCal `GEO` value
+    pub fn to_ical(&self) -> String {
+        format!("{};{}", self.latitude(), self.longitude())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Coordinates;
+
+    #[test]
+    fn rounds_to_microdegrees() {
+        let c = Coordinates::new(32.79876543, -117.2).unwrap();
+        assert_eq!(c.latitude(), 32.798765);
+        assert_eq!(c.to_ical(), "32.798765;-117.2");
+        assert_eq!(Coordinates::parse("32.7987650", " -117.20"), Some(c));
+    }
+
+    #[test]
+    fn off_the_map() {
+        assert_eq!(Coordinates::new(91.0, 0.0), None);
+        assert_eq!(Coordinates::new(0.0, -180.5), None);
+        assert_eq!(Coordinates::new(f64::NAN, 0.0), None);
+        assert_eq!(Coordinates::parse("north", "0"), None);
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use itertools::Itertools;
+
+use crate::base_url::BaseUrl;
+use crate::options::RenderOptions;
+use crate::pickup_calendar::PickupCalendar;
+use crate::render::Renderer;
+use crate::subscribe::SubscriptionLinks;
+
+// Everything is inlined so the page works without any other requests
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:36rem;margin:2rem auto;padding:0 1rem;color:#222}\
+h1{font-size:1.5rem}\
+ul{list-style:none;padding:0}\
+li{padding:.5rem 0;border-bottom:1px solid #ddd}\
+.date{font-weight:bold}\
+.links a{display:inline-block;margin:0 1rem .5rem 0}";
+
+/// Escape text for use in HTML element content and attribute values
+pub(crate) fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl PickupCalendar {
+    pub fn to_html(&self, base_url: &BaseUrl, options: &RenderOptions) -> String {
+        let today = Utc::now().with_timezone(&options.timezone).date_naive();
+        self.to_html_from(base_url, today, options)
+    }
+
+    fn to_html_from(
+        &self,
+        base_url: &BaseUrl,
+        today: NaiveDate,
+        options: &RenderOptions,
+    ) -> String {
+        let links = SubscriptionLinks::new(base_url, &self.id);
+
+        let pickups = self
+            .pickups
+            .iter()
+            .filter(|p| p.date >= today)
+            .chunk_by(|p| p.date)
+            .into_iter()
+            .map(|(date, pickups)| {
+                let names = pickups
+                    .map(|p| escape(&options.pickup_name(p.name)))
+                    .join(", ");
+
+                format!(
+                    "<li><span class=\"date\">{}</span>: {names}</li>",
+                    date.format("%A, %B %-d")
+                )
+            })
+            .join("\n");
+
+        let pickups = if pickups.is_empty() {
+            "<li>No upcoming pickups</li>".to_string()
+        } else {
+            pickups
+        };
+
+        format!(
+            "<!DOCTYPE html>
+<html lang=\"{lang}\">
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>Trashcal: {address}</title>
+<style>{STYLE}</style>
+</head>
+<body>
+<h1>Trashcal</h1>
+<p>{address}</p>
+<ul>
+{pickups}
+</ul>
+<p class=\"links\">
+<a href=\"{webcal}\">Subscribe</a>
+<a href=\"{google}\">Add to Google Calendar</a>
+<a href=\"{outlook}\">Add to Outlook</a>
+</p>
+</body>
+</html>
+",
+            address = escape(&self.address),
+            lang = options.lang,
+            webcal = escape(&links.webcal),
+            google = escape(&links.google),
+            outlook = escape(&links.outlook),
+        )
+    }
+}
+
+pub struct HtmlRenderer;
+
+impl Renderer for HtmlRenderer {
+    fn content_type(&self) -> &'static str {
+        "text/html;charset=UTF-8"
+    }
+
+    fn render(
+        &self,
+        calendar: &PickupCalendar,
+        base_url: &BaseUrl,
+        options: &RenderOptions,
+    ) -> Result<String> {
+        Ok(calendar.to_html(base_url, options))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::escape;
+    use crate::base_url::BaseUrl;
+    use crate::fixtures::{calendar, calendar_at, date, TWO_WEEKS};
+    use crate::options::RenderOptions;
+
+    #[test]
+    fn escapes_markup() {
+        assert_eq!(
+            escape("<a href=\"x\">&'"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;"
+        );
+    }
+
+    #[test]
+    fn lists_upcoming_pickups_grouped_by_date() {
+        let today = date(3);
+        let html = calendar_at("1234 <AGATE> ST", TWO_WEEKS).to_html_from(
+            &BaseUrl::default(),
+            today,
+            &RenderOptions::with_whimsy(false),
+        );
+
+        assert!(html.contains("1234 &lt;AGATE&gt; ST"));
+        assert!(!html.contains("Monday, January 2"));
+        assert!(html.contains("Monday, January 9</span>: Recyclables, Trash"));
+    }
+
+    #[test]
+    fn includes_subscribe_links() {
+        let today = date(1);
+        let html = calendar(TWO_WEEKS).to_html_from(
+            &BaseUrl::default(),
+            today,
+            &RenderOptions::with_whimsy(true),
+        );
+
+        assert!(html.contains("href=\"webcal://stabbylambda.com/trashcal/a4Ot0000001E8i4EAC\""));
+        assert!(html.contains(
+            "https://calendar.google.com/calendar/r?cid=webcal%3A%2F%2Fstabbylambda.com%2Ftrashcal%2Fa4Ot0000001E8i4EAC"
+        ));
+        assert!(html.contains("addfromweb?url=https%3A%2F%2Fstabbylambda.com"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("<link"));
+    }
+}
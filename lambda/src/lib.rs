@@ -1,71 +1,395 @@
 use anyhow::Result;
+use chrono::Utc;
 
+use crate::base_url::BaseUrl;
+use crate::canary::latest_report;
+use crate::changes::HistoryStore;
+use crate::collection_id::CollectionId;
+use crate::cors::{AllowedOrigins, ALLOWED_METHODS};
+use crate::error::Error;
+use crate::format::OutputFormat;
+use crate::metrics::Metrics;
+use crate::next::{next_midnight, today, NextPickup};
+use crate::options::RenderOptions;
+use crate::pickup_calendar::{expires_header, internet_message_format};
+use crate::render::renderer;
+use crate::subscribe::SubscriptionLinks;
 use crate::trashcal::trashcal;
-use http::header::{CONTENT_DISPOSITION, CONTENT_TYPE, EXPIRES};
-use http::StatusCode;
+use http::header::{
+    ALLOW, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, EXPIRES, LOCATION, RETRY_AFTER,
+};
+use http::{Method, StatusCode};
+use itertools::Itertools;
 use lambda_http::{Body, Request, RequestExt, Response};
 use lambda_runtime::tracing::info;
 
-use tracing::instrument;
+use tracing::{info_span, instrument};
+use url::form_urlencoded;
 
+pub mod address;
+pub mod base_url;
+pub mod canary;
+pub mod changes;
+pub mod client;
+pub mod collection_id;
+pub mod config;
+pub mod cors;
+pub mod digest;
 pub mod error;
+pub mod export;
+pub mod feed;
+#[cfg(test)]
+mod fixtures;
+pub mod format;
+pub mod geo;
+pub mod html;
+pub mod metrics;
+pub mod next;
+pub mod notify;
+pub mod options;
+pub mod page;
 pub mod pickup;
 pub mod pickup_calendar;
+pub mod redact;
+pub mod render;
+pub mod retry;
+pub mod s3;
+pub mod subscribe;
+pub mod telemetry;
 pub mod trashcal;
 
-#[instrument]
-pub async fn get_trashcal(id: &str, whimsy: bool) -> Result<Response<Body>> {
-    // Format is chosen by the URL suffix alone, never the Accept header: CloudFront's
-    // cache key always includes the path, so `.json` and the default (iCal) land in
-    // separate cache entries instead of colliding behind one URL.
-    let is_json_request = id.contains(".json");
+#[instrument(
+    skip_all,
+    fields(collection_id = %redact::id(id.as_str()), %format, options = %redact::options(options), head = head)
+)]
+pub async fn get_trashcal(
+    id: &CollectionId,
+    format: OutputFormat,
+    options: &RenderOptions,
+    base_url: &BaseUrl,
+    head: bool,
+) -> Result<Response<Body>> {
+    let calendar = options.filter(trashcal(id).await?);
 
-    let calendar = trashcal(id).await?;
+    info!(
+        message = "Returning calendar",
+        %format,
+        address = %redact::address(&calendar.address),
+        pickup_dates = ?calendar.pickups.iter().map(|p| p.date).collect::<Vec<_>>()
+    );
 
-    // build the response as either json or calendar
-    let resp = Response::builder().status(StatusCode::OK);
-    let resp = if is_json_request {
-        info!(
-            message = "Returning calendar as JSON", 
-            address = %calendar.address,
-            pickup_dates = ?calendar.pickups.iter().map(|p| p.date).collect::<Vec<_>>()
+    // a HEAD request only wants the headers, so don't bother rendering
+    let renderer = renderer(format);
+    Metrics::new()
+        .dimension("format", format)
+        .count("total", 1)
+        .emit();
+    let body = if head {
+        Body::Empty
+    } else {
+        let span = info_span!(
+            "render",
+            collection_id = %redact::id(id.as_str()),
+            %format,
+            events = calendar.pickups.len()
         );
-        let json = serde_json::to_string_pretty(&calendar)?;
+        span.in_scope(|| renderer.render(&calendar, base_url, options))?
+            .into()
+    };
 
-        resp.header(CONTENT_TYPE, "application/json")
-            .header(EXPIRES, calendar.expires_header())
-            .body(json.into())
-    } else {
-        info!(
-            message = "Returning calendar as iCal",
-            address = %calendar.address,
-            pickup_dates = ?calendar.pickups.iter().map(|p| p.date).collect::<Vec<_>>()
+    let resp = Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, renderer.content_type())
+        .header(
+            EXPIRES,
+            expires_header(renderer.valid_until(&calendar, options, Utc::now())),
         );
-        let expires = calendar.expires_header();
-        let calendar = calendar.to_calendar(whimsy)?;
+    let resp = match renderer.content_disposition() {
+        Some(disposition) => resp.header(CONTENT_DISPOSITION, disposition),
+        None => resp,
+    };
+    Ok(resp.body(body)?)
+}
+
+/// Sends a request to the one URL for this calendar: the format as a suffix and the options in
+/// canonical order
+pub fn canonical_redirect(
+    base_url: &BaseUrl,
+    id: &CollectionId,
+    format: OutputFormat,
+    options: &RenderOptions,
+) -> Result<Response<Body>> {
+    // iCal is the bare path, since that's what everyone's calendar app is subscribed to
+    let mut location = match format {
+        OutputFormat::ICal => base_url.calendar_url(id.as_str()),
+        _ => base_url.calendar_url(&format!("{id}.{}", format.extension())),
+    };
+    let query = options.to_query();
+    if !query.is_empty() {
+        location = format!("{location}?{query}");
+    }
+
+    // the redirect only exists to fix up the URL, so don't let anybody hang on to it
+    let resp = Response::builder()
+        .status(StatusCode::FOUND)
+        .header(LOCATION, location)
+        .header(CACHE_CONTROL, "no-store")
+        .body(Body::Empty);
+    Ok(resp?)
+}
+
+#[instrument(skip_all, fields(collection_id = %redact::id(id), text = is_text_request))]
+pub async fn get_next_pickup(
+    id: &str,
+    is_text_request: bool,
+    options: &RenderOptions,
+) -> Result<Response<Body>> {
+    let id: CollectionId = id.parse()?;
+    let calendar = trashcal(&id).await?;
+    let today = today();
+    let next = NextPickup::from_calendar(&calendar, today);
+    let expires = next_midnight(today)
+        .map(|d| internet_message_format(&d))
+        .unwrap_or_else(|| "0".to_string());
+
+    let Some(next) = next else {
+        // nothing upcoming means the page didn't parse into anything useful
+        let resp = Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header(EXPIRES, "0")
+            .body(Body::Empty);
+        return Ok(resp?);
+    };
+
+    info!(
+        message = "Returning next pickup",
+        address = %redact::address(&calendar.address),
+        date = %next.date
+    );
+
+    let resp = Response::builder().status(StatusCode::OK);
+    let resp = if is_text_request {
+        resp.header(CONTENT_TYPE, "text/plain;charset=UTF-8")
+            .header(EXPIRES, expires)
+            .body(next.display_string(options).into())
+    } else {
+        let json = serde_json::to_string(&next)?;
 
-        resp.header(CONTENT_TYPE, "text/calendar;charset=UTF-8")
-            .header(CONTENT_DISPOSITION, "attachment; filename=trashcal.ics")
+        resp.header(CONTENT_TYPE, "application/json")
             .header(EXPIRES, expires)
-            .body(calendar.to_string().into())
+            .body(json.into())
+    };
+    Ok(resp?)
+}
+
+#[instrument(skip_all, fields(collection_id = %redact::id(id)))]
+pub async fn get_changes(id: &str) -> Result<Response<Body>> {
+    let id: CollectionId = id.parse()?;
+    // scraping records whatever changed since last time, so look right after
+    trashcal(&id).await?;
+    let history = HistoryStore::from_env().await.load(id.as_str()).await?;
+
+    // the next scrape could turn up a change, so nobody gets to hang on to this
+    let json = serde_json::to_string_pretty(&history.changes)?;
+    let resp = Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CACHE_CONTROL, "no-store")
+        .body(json.into());
+    Ok(resp?)
+}
+
+#[instrument(skip_all, fields(collection_id = %redact::id(id)))]
+pub fn subscribe(id: &str, base_url: &BaseUrl) -> Result<Response<Body>> {
+    let resp = Response::builder();
+    let resp = match id.strip_suffix(".json") {
+        // the JSON listing is for building "add to calendar" buttons
+        Some(id) => {
+            let id: CollectionId = id.parse()?;
+            let links = SubscriptionLinks::new(base_url, id.as_str());
+            let json = serde_json::to_string_pretty(&links)?;
+
+            resp.status(StatusCode::OK)
+                .header(CONTENT_TYPE, "application/json")
+                .body(json.into())
+        }
+        // everything else gets bounced to webcal:// so the OS hands it to a calendar app
+        // that subscribes instead of doing a one-time import
+        None => {
+            let id: CollectionId = id.parse()?;
+            let links = SubscriptionLinks::new(base_url, id.as_str());
+
+            resp.status(StatusCode::FOUND)
+                .header(LOCATION, links.webcal)
+                .body(Body::Empty)
+        }
     };
     Ok(resp?)
 }
 
+/// The process is up and can answer; says nothing about the city
+pub fn healthz() -> Result<Response<Body>> {
+    let json = serde_json::json!({ "status": "ok", "version": env!("CARGO_PKG_VERSION") });
+    let resp = Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CACHE_CONTROL, "no-store")
+        .body(json.to_string().into());
+    Ok(resp?)
+}
+
+/// Whether the scraper still works against the real city site, for uptime monitors to alarm on.
+/// Anyone can ask, so the answer is reused for a minute rather than scraped every time.
+pub async fn canary() -> Result<Response<Body>> {
+    let (report, ttl) = latest_report().await;
+    let status = if report.ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    let json = serde_json::to_string_pretty(&report)?;
+    let resp = Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CACHE_CONTROL, format!("max-age={}", ttl.as_secs()))
+        .body(json.into());
+    Ok(resp?)
+}
+
+/// The city being down (or the caller asking for nonsense) isn't our bug, so say so with a real
+/// status instead of blowing up
+fn error_response(e: &Error) -> Option<http::Result<Response<Body>>> {
+    let status = e.upstream_status().or_else(|| e.client_status())?;
+    let resp = Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain;charset=UTF-8");
+
+    // hold off everyone (CloudFront included) for as long as the city asked us to, and
+    // otherwise make sure nobody caches the failure
+    let resp = match e.retry_after() {
+        Some(seconds) => resp
+            .header(RETRY_AFTER, seconds)
+            .header(CACHE_CONTROL, format!("public, max-age={seconds}")),
+        None => resp.header(CACHE_CONTROL, "no-store"),
+    };
+
+    Some(resp.header(EXPIRES, "0").body(e.to_string().into()))
+}
+
 pub async fn trashcal_handler(event: Request) -> Result<Response<Body>> {
-    // get the ID
+    let cors = AllowedOrigins::from_env();
+    let mut resp = match *event.method() {
+        // browsers ask before they fetch from another origin
+        Method::OPTIONS => return Ok(cors.preflight(&event)?),
+        Method::GET | Method::HEAD => match route(&event).await {
+            Err(e) => {
+                let error = e.downcast_ref::<Error>();
+                let kind = error.map(Error::kind).unwrap_or("Other");
+                Metrics::new()
+                    .dimension("error", kind)
+                    .count("errors", 1)
+                    .emit();
+
+                match error.and_then(error_response) {
+                    Some(resp) => resp?,
+                    None => return Err(e),
+                }
+            }
+            Ok(resp) => resp,
+        },
+        // nothing here changes anything, so nothing else gets to scrape the city
+        _ => Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header(ALLOW, ALLOWED_METHODS)
+            .header(CACHE_CONTROL, "no-store")
+            .body(Body::Empty)?,
+    };
+
+    // HEAD gets exactly the headers GET would, and nothing else
+    if event.method() == Method::HEAD {
+        *resp.body_mut() = Body::Empty;
+    }
+    cors.apply(&event, &mut resp);
+    Ok(resp)
+}
+
+async fn route(event: &Request) -> Result<Response<Body>> {
+    // monitoring doesn't care about any of the calendar machinery
+    match event.raw_http_path() {
+        "/healthz" => return healthz(),
+        "/canary" => return canary().await,
+        _ => {}
+    }
+
+    let base_url = BaseUrl::from_env();
     let params = event.path_parameters();
     let query = event.query_string_parameters();
+
+    // whimsy, types, language and friends; a bad value is a 400 for every route
+    let options = RenderOptions::from_query(&query)?;
+
+    // subscribe links, the next pickup and schedule changes get their own routes,
+    // everything else is a calendar
+    let path = event.raw_http_path();
+    if let Some(id) = path.strip_prefix("/subscribe/") {
+        return subscribe(id, &base_url);
+    }
+    if let Some(id) = path.strip_suffix("/next") {
+        return get_next_pickup(id.trim_start_matches('/'), false, &options).await;
+    }
+    if let Some(id) = path.strip_suffix("/next.txt") {
+        return get_next_pickup(id.trim_start_matches('/'), true, &options).await;
+    }
+    if let Some(id) = path.strip_suffix("/changes") {
+        return get_changes(id.trim_start_matches('/')).await;
+    }
+    // that's all there is under an ID, so anything else is as missing as an unknown extension
+    if let Some((_, sub_path)) = path.trim_start_matches('/').split_once('/') {
+        return Err(Error::UnknownPath(sub_path.to_string()).into());
+    }
+
+    // get the ID
     let id = params
         .first("id")
         .or_else(|| query.first("id"))
         .unwrap_or("null");
 
-    // get the whimsy parameter, defaults to true
-    let whimsy = query
-        .first("whimsy")
-        .map(|v| v != "false")
-        .unwrap_or(true);
+    // Format is chosen by the URL suffix, never the Accept header: CloudFront's cache key
+    // always includes the path, so `.json` and the default (iCal) land in separate cache
+    // entries instead of colliding behind one URL. That goes for browsers too: an
+    // `Accept: text/html` page would get cached under the bare path and handed to calendar
+    // clients, so the HTML page lives at `.html`.
+    let (id, format) = OutputFormat::split(id)?;
+    let id: CollectionId = id.parse()?;
+
+    // `?format=` only ever redirects to the suffix, so each format has exactly one URL
+    if let Some(format) = query.first("format") {
+        let format = OutputFormat::from_query(format)?;
+        return canonical_redirect(&base_url, &id, format, &options);
+    }
+
+    // Same goes for the options: every spelling of the same calendar (reordered, defaults spelled
+    // out, params we don't know) gets sent to the canonical query so they all share one cache entry
+    let given = event
+        .uri()
+        .query()
+        .map(|q| {
+            form_urlencoded::parse(q.as_bytes())
+                .filter(|(k, _)| k != "id")
+                .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                .collect_vec()
+        })
+        .unwrap_or_default();
+    let canonical = options
+        .canonical_query()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect_vec();
+    if given != canonical {
+        return canonical_redirect(&base_url, &id, format, &options);
+    }
 
-    get_trashcal(id, whimsy).await
+    let head = event.method() == Method::HEAD;
+    get_trashcal(&id, format, &options, &base_url, head).await
 }
@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+/// Matches the namespace the CDK stack alarms and dashboards look in
+pub const NAMESPACE: &str = "trashcal";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub enum Unit {
+    Count,
+    Milliseconds,
+}
+
+/// A few metrics that share the same dimensions, printed to stdout as one CloudWatch Embedded
+/// Metric Format record. Lambda ships stdout to CloudWatch Logs, which turns the record into
+/// metrics on its own, so there's no API call and nothing to batch.
+#[derive(Debug, Default, Clone)]
+pub struct Metrics {
+    dimensions: Vec<(&'static str, String)>,
+    values: Vec<(&'static str, f64, Unit)>,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    pub fn dimension(mut self, name: &'static str, value: impl ToString) -> Metrics {
+        self.dimensions.push((name, value.to_string()));
+        self
+    }
+
+    pub fn count(mut self, name: &'static str, value: usize) -> Metrics {
+        self.values.push((name, value as f64, Unit::Count));
+        self
+    }
+
+    pub fn duration(mut self, name: &'static str, value: Duration) -> Metrics {
+        self.values
+            .push((name, value.as_secs_f64() * 1000.0, Unit::Milliseconds));
+        self
+    }
+
+    pub fn to_emf(&self, timestamp: DateTime<Utc>) -> Value {
+        // every metric is also published without dimensions, so there's always a grand total
+        let mut dimension_sets = vec![vec![]];
+        if !self.dimensions.is_empty() {
+            dimension_sets.push(self.dimensions.iter().map(|(name, _)| *name).collect());
+        }
+
+        let definitions = self
+            .values
+            .iter()
+            .map(|(name, _, unit)| json!({ "Name": name, "Unit": unit }))
+            .collect::<Vec<_>>();
+
+        let mut record = Map::new();
+        record.insert(
+            "_aws".to_string(),
+            json!({
+                "Timestamp": timestamp.timestamp_millis(),
+                "CloudWatchMetrics": [{
+                    "Namespace": NAMESPACE,
+                    "Dimensions": dimension_sets,
+                    "Metrics": definitions,
+                }],
+            }),
+        );
+        for (name, value) in &self.dimensions {
+            record.insert(name.to_string(), json!(value));
+        }
+        for (name, value, _) in &self.values {
+            record.insert(name.to_string(), json!(value));
+        }
+        Value::Object(record)
+    }
+
+    pub fn emit(&self) {
+        if !self.values.is_empty() {
+            println!("{}", self.to_emf(Utc::now()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use chrono::DateTime;
+    use serde_json::json;
+
+    use super::Metrics;
+
+    #[test]
+    fn emf_record() {
+        let timestamp = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let record = Metrics::new()
+            .dimension("format", "json")
+            .count("total", 1)
+            .duration("upstream_latency", Duration::from_millis(1500))
+            .to_emf(timestamp);
+
+        assert_eq!(
+            record,
+            json!({
+                "_aws": {
+                    "Timestamp": 1_700_000_000_000i64,
+                    "CloudWatchMetrics": [{
+                        "Namespace": "trashcal",
+                        "Dimensions": [[], ["format"]],
+                        "Metrics": [
+                            { "Name": "total", "Unit": "Count" },
+                            { "Name": "upstream_latency", "Unit": "Milliseconds" },
+                        ],
+                    }],
+                },
+                "format": "json",
+                "total": 1.0,
+                "upstream_latency": 1500.0,
+            })
+        );
+    }
+
+    #[test]
+    fn no_dimensions() {
+        let timestamp = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let record = Metrics::new().count("stale_served", 1).to_emf(timestamp);
+        assert_eq!(
+            record["_aws"]["CloudWatchMetrics"][0]["Dimensions"],
+            json!([[]])
+        );
+    }
+}
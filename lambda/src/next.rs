@@ -0,0 +1,167 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, TimeZone, Utc};
+use chrono_tz::US::Pacific;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::options::RenderOptions;
+use crate::pickup::PickupType;
+use crate::pickup_calendar::PickupCalendar;
+
+/// Just enough to answer "what's tomorrow?" for dashboards and home automation
+#[derive(Serialize, Debug, Eq, PartialEq)]
+pub struct NextPickup {
+    pub date: NaiveDate,
+    pub types: Vec<PickupType>,
+    pub days_until: u64,
+    /// Whether recycling gets picked up this week (Monday to Sunday, San Diego time)
+    pub recycling_this_week: bool,
+}
+
+/// Today's date as far as San Diego is concerned
+pub fn today() -> NaiveDate {
+    Utc::now().with_timezone(&Pacific).date_naive()
+}
+
+/// The next pickup answer goes stale at midnight, even if the calendar doesn't
+pub fn next_midnight(today: NaiveDate) -> Option<DateTime<Utc>> {
+    let tomorrow = today.checked_add_days(Days::new(1))?;
+    let midnight = tomorrow.and_hms_opt(0, 0, 0)?;
+
+    // DST changes at 2AM, so midnight always exists exactly once
+    Pacific
+        .from_local_datetime(&midnight)
+        .single()
+        .map(|d| d.to_utc())
+}
+
+impl NextPickup {
+    pub fn from_calendar(calendar: &PickupCalendar, today: NaiveDate) -> Option<NextPickup> {
+        let (date, pickups) = calendar
+            .pickups
+            .iter()
+            .filter(|p| p.date >= today)
+            .chunk_by(|p| p.date)
+            .into_iter()
+            .next()
+            .map(|(date, pickups)| (date, pickups.collect_vec()))?;
+
+        let week = today.iso_week();
+        let recycling_this_week = calendar
+            .pickups
+            .iter()
+            .any(|p| p.name == PickupType::Recyclables && p.date.iso_week() == week);
+
+        Some(NextPickup {
+            date,
+            types: pickups.iter().map(|p| p.name).collect(),
+            days_until: (date - today).num_days().unsigned_abs(),
+            recycling_this_week,
+        })
+    }
+
+    pub fn display_string(&self, options: &RenderOptions) -> String {
+        let weekday = options.lang.weekday(self.date.weekday());
+        let when = options.lang.days_from_now(self.days_until);
+        let types = self
+            .types
+            .iter()
+            .map(|t| options.pickup_name(*t))
+            .join(", ");
+
+        format!("{weekday} {} ({when}): {types}", self.date)
+    }
+}
+
+impl Display for NextPickup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.display_string(&RenderOptions::default()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{next_midnight, NextPickup};
+    use crate::fixtures::{self, date};
+    use crate::options::{Lang, RenderOptions};
+    use crate::pickup::PickupType;
+    use crate::pickup_calendar::PickupCalendar;
+
+    fn calendar() -> PickupCalendar {
+        fixtures::calendar(&[
+            (PickupType::Organics, 2),
+            (PickupType::Trash, 2),
+            (PickupType::Recyclables, 9),
+            (PickupType::Organics, 9),
+            (PickupType::Trash, 9),
+        ])
+    }
+
+    #[test]
+    fn next_pickup_today() {
+        let next = NextPickup::from_calendar(&calendar(), date(2)).unwrap();
+
+        assert_eq!(
+            next,
+            NextPickup {
+                date: date(2),
+                types: vec![PickupType::Organics, PickupType::Trash],
+                days_until: 0,
+                recycling_this_week: false,
+            }
+        );
+    }
+
+    #[test]
+    fn next_pickup_skips_past_dates() {
+        let next = NextPickup::from_calendar(&calendar(), date(8)).unwrap();
+
+        assert_eq!(next.date, date(9));
+        assert_eq!(next.days_until, 1);
+        assert_eq!(
+            next.display_string(&RenderOptions::with_whimsy(false)),
+            "Monday 2023-01-09 (tomorrow): Recyclables, Organics, Trash"
+        );
+        let spanish = RenderOptions {
+            lang: Lang::Es,
+            ..RenderOptions::with_whimsy(false)
+        };
+        assert_eq!(
+            next.display_string(&spanish),
+            "lunes 2023-01-09 (mañana): Reciclables, Orgánicos, Basura"
+        );
+    }
+
+    #[test]
+    fn recycling_this_week_is_about_today() {
+        // Sunday the 8th is still the week of the 2nd, so Monday's recycling is next week
+        assert!(
+            !NextPickup::from_calendar(&calendar(), date(8))
+                .unwrap()
+                .recycling_this_week
+        );
+        assert!(
+            !NextPickup::from_calendar(&calendar(), date(3))
+                .unwrap()
+                .recycling_this_week
+        );
+        assert!(
+            NextPickup::from_calendar(&calendar(), date(9))
+                .unwrap()
+                .recycling_this_week
+        );
+    }
+
+    #[test]
+    fn no_next_pickup() {
+        assert_eq!(NextPickup::from_calendar(&calendar(), date(10)), None);
+    }
+
+    #[test]
+    fn stale_at_pacific_midnight() {
+        let midnight = next_midnight(date(1)).unwrap();
+        assert_eq!(midnight.to_rfc3339(), "2023-01-02T08:00:00+00:00");
+    }
+}
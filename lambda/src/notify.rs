@@ -0,0 +1,389 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use anyhow::{ensure, Result};
+use chrono::{Days, NaiveDate};
+use itertools::Itertools;
+use lambda_runtime::tracing::{info, warn};
+use lambda_runtime::LambdaEvent;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use strum::{Display, EnumString};
+use tokio::sync::OnceCell;
+
+use crate::changes::HISTORY_BUCKET_VAR;
+use crate::client::client;
+use crate::config::{default_whimsy, list_from_env};
+use crate::error::Error;
+use crate::next::today;
+use crate::pickup::PickupType;
+use crate::pickup_calendar::PickupCalendar;
+use crate::redact;
+use crate::s3;
+use crate::trashcal::fetch_calendar;
+
+/// A JSON array of webhook subscriptions, e.g. `[{"id": "a4O...", "url": "https://hooks.slack.com/..."}]`
+pub const WEBHOOKS_VAR: &str = "TRASHCAL_WEBHOOKS";
+
+/// Lets receivers drop duplicate deliveries of the same reminder
+pub const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+#[derive(
+    EnumString, Display, Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum WebhookFormat {
+    #[default]
+    Json,
+    Slack,
+    Discord,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct WebhookSubscription {
+    pub id: String,
+    pub url: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    #[serde(default = "default_whimsy")]
+    pub whimsy: bool,
+}
+
+impl WebhookSubscription {
+    pub fn from_env() -> Result<Vec<WebhookSubscription>> {
+        list_from_env(WEBHOOKS_VAR)
+    }
+}
+
+/// The day-before heads up for a single calendar
+#[derive(Serialize, Debug, Eq, PartialEq, Clone)]
+pub struct Reminder {
+    pub id: String,
+    pub address: String,
+    pub date: NaiveDate,
+    pub types: Vec<PickupType>,
+}
+
+impl Reminder {
+    /// Pickups happening the day after `today`, if there are any
+    pub fn for_tomorrow(calendar: &PickupCalendar, today: NaiveDate) -> Option<Reminder> {
+        let tomorrow = today.checked_add_days(Days::new(1))?;
+        let types = calendar
+            .pickups
+            .iter()
+            .filter(|p| p.date == tomorrow)
+            .map(|p| p.name)
+            .collect_vec();
+
+        (!types.is_empty()).then(|| Reminder {
+            id: calendar.id.clone(),
+            address: calendar.address.clone(),
+            date: tomorrow,
+            types,
+        })
+    }
+
+    pub fn idempotency_key(&self) -> String {
+        format!("{}:{}", self.id, self.date)
+    }
+
+    pub fn message(&self, whimsy: bool) -> String {
+        let types = self
+            .types
+            .iter()
+            .map(|t| t.display_string(whimsy))
+            .join(", ");
+
+        format!("Tomorrow is trash day at {}: {types}", self.address)
+    }
+
+    pub fn payload(&self, format: WebhookFormat, whimsy: bool) -> Value {
+        match format {
+            WebhookFormat::Json => json!({
+                "id": self.id,
+                "address": self.address,
+                "date": self.date,
+                "types": self.types.iter().map(|t| t.display_string(whimsy)).collect_vec(),
+                "message": self.message(whimsy),
+            }),
+            WebhookFormat::Slack => json!({ "text": self.message(whimsy) }),
+            WebhookFormat::Discord => json!({ "content": self.message(whimsy) }),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Delivery {
+    Sent,
+    AlreadySent,
+}
+
+/// Which reminders have gone out. Slack and Discord don't look at `Idempotency-Key`, so
+/// this is the only thing keeping EventBridge's retries from reminding people twice.
+#[derive(Debug)]
+pub enum SentLog {
+    /// Only this container's sends, for running locally
+    Memory(Mutex<HashSet<String>>),
+    /// One empty object per send in the history bucket, so every container sees them
+    S3 {
+        client: aws_sdk_s3::Client,
+        bucket: String,
+    },
+}
+
+impl SentLog {
+    pub async fn from_env() -> SentLog {
+        match std::env::var(HISTORY_BUCKET_VAR) {
+            Ok(bucket) => SentLog::S3 {
+                client: s3::client().await.clone(),
+                bucket,
+            },
+            Err(_) => SentLog::Memory(Default::default()),
+        }
+    }
+
+    /// Claims a send before it happens, returning false if somebody already did
+    async fn claim(&self, key: &str) -> Result<bool> {
+        match self {
+            SentLog::Memory(sent) => Ok(sent.lock().unwrap().insert(key.to_string())),
+            SentLog::S3 { client, bucket } => {
+                let response = client
+                    .put_object()
+                    .bucket(bucket)
+                    .key(key)
+                    .if_none_match("*")
+                    .send()
+                    .await;
+                match response {
+                    Ok(_) => Ok(true),
+                    Err(e) if s3::lost_race(&e) => Ok(false),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        }
+    }
+
+    /// Gives up a claim when the send failed, so the next try can have it
+    async fn release(&self, key: &str) -> Result<()> {
+        match self {
+            SentLog::Memory(sent) => {
+                sent.lock().unwrap().remove(key);
+            }
+            SentLog::S3 { client, bucket } => {
+                client
+                    .delete_object()
+                    .bucket(bucket)
+                    .key(key)
+                    .send()
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// FNV-1a, which is plenty to tell webhook URLs apart without writing them anywhere
+fn url_hash(url: &str) -> u64 {
+    url.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Posts reminders, skipping ones that were already sent
+#[derive(Debug)]
+pub struct Notifier {
+    client: reqwest::Client,
+    sent: SentLog,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Notifier {
+            client: client().clone(),
+            sent: SentLog::Memory(Default::default()),
+        }
+    }
+}
+
+impl Notifier {
+    pub fn new() -> Notifier {
+        Notifier::default()
+    }
+
+    pub async fn from_env() -> Notifier {
+        Notifier {
+            client: client().clone(),
+            sent: SentLog::from_env().await,
+        }
+    }
+
+    /// Where the send of this reminder to this webhook gets recorded
+    fn sent_key(subscription: &WebhookSubscription, reminder: &Reminder) -> Result<String> {
+        ensure!(
+            reminder.id.chars().all(|c| c.is_ascii_alphanumeric()),
+            Error::IdError(reminder.id.clone())
+        );
+        Ok(format!(
+            "sent/{}/{:016x}",
+            reminder.idempotency_key(),
+            url_hash(&subscription.url)
+        ))
+    }
+
+    pub async fn notify(
+        &self,
+        subscription: &WebhookSubscription,
+        reminder: &Reminder,
+    ) -> Result<Delivery> {
+        let key = Notifier::sent_key(subscription, reminder)?;
+        if !self.sent.claim(&key).await? {
+            return Ok(Delivery::AlreadySent);
+        }
+
+        let payload = reminder.payload(subscription.format, subscription.whimsy);
+        let posted = self
+            .client
+            .post(&subscription.url)
+            .header(IDEMPOTENCY_KEY, reminder.idempotency_key())
+            .json(&payload)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+
+        if let Err(e) = posted {
+            if let Err(release) = self.sent.release(&key).await {
+                warn!(
+                    message = "Couldn't release reminder claim",
+                    error = %redact::text(&release.to_string(), &reminder.id)
+                );
+            }
+            return Err(Error::from(e).into());
+        }
+        Ok(Delivery::Sent)
+    }
+}
+
+/// Scheduled entry point: send tomorrow's reminders to every configured webhook
+pub async fn notify_handler(_event: LambdaEvent<Value>) -> Result<Value> {
+    static NOTIFIER: OnceCell<Notifier> = OnceCell::const_new();
+    let notifier = NOTIFIER.get_or_init(Notifier::from_env).await;
+
+    let subscriptions = WebhookSubscription::from_env()?;
+    let today = today();
+    let mut sent = 0;
+
+    for subscription in &subscriptions {
+        let calendar = match fetch_calendar(&subscription.id).await {
+            Ok(calendar) => calendar,
+            Err(e) => {
+                warn!(message = "Couldn't get calendar for webhook", collection_id = %redact::id(&subscription.id), error = %redact::text(&e.to_string(), &subscription.id));
+                continue;
+            }
+        };
+
+        let Some(reminder) = Reminder::for_tomorrow(&calendar, today) else {
+            continue;
+        };
+
+        match notifier.notify(subscription, &reminder).await {
+            Ok(Delivery::Sent) => sent += 1,
+            Ok(Delivery::AlreadySent) => {}
+            Err(e) => {
+                warn!(message = "Couldn't send webhook", collection_id = %redact::id(&subscription.id), error = %redact::text(&e.to_string(), &subscription.id))
+            }
+        }
+    }
+
+    info!(message = "Sent reminders", sent);
+    Ok(json!({ "sent": sent }))
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{Notifier, Reminder, SentLog, WebhookFormat, WebhookSubscription};
+    use crate::fixtures::{self, date};
+    use crate::pickup::PickupType;
+    use crate::pickup_calendar::PickupCalendar;
+
+    fn calendar() -> PickupCalendar {
+        fixtures::calendar(&[(PickupType::Recyclables, 2), (PickupType::Trash, 2)])
+    }
+
+    #[test]
+    fn reminder_for_tomorrow() {
+        let reminder = Reminder::for_tomorrow(&calendar(), date(1)).unwrap();
+
+        assert_eq!(reminder.date, date(2));
+        assert_eq!(
+            reminder.types,
+            vec![PickupType::Recyclables, PickupType::Trash]
+        );
+        assert_eq!(reminder.idempotency_key(), "a4Ot0000001E8i4EAC:2023-01-02");
+    }
+
+    #[test]
+    fn no_reminder_without_pickups_tomorrow() {
+        assert_eq!(Reminder::for_tomorrow(&calendar(), date(2)), None);
+    }
+
+    #[test]
+    fn payload_formats() {
+        let reminder = Reminder::for_tomorrow(&calendar(), date(1)).unwrap();
+        let message = "Tomorrow is trash day at 1234 AGATE ST: Recyclables, Trash";
+
+        assert_eq!(
+            reminder.payload(WebhookFormat::Slack, false),
+            json!({ "text": message })
+        );
+        assert_eq!(
+            reminder.payload(WebhookFormat::Discord, false),
+            json!({ "content": message })
+        );
+        assert_eq!(
+            reminder.payload(WebhookFormat::Json, false)["date"],
+            "2023-01-02"
+        );
+    }
+
+    #[tokio::test]
+    async fn claims_once_until_released() {
+        let sent = SentLog::Memory(Default::default());
+
+        assert!(sent.claim("sent/foo").await.unwrap());
+        assert!(!sent.claim("sent/foo").await.unwrap());
+
+        sent.release("sent/foo").await.unwrap();
+        assert!(sent.claim("sent/foo").await.unwrap());
+    }
+
+    #[test]
+    fn sent_key_per_webhook() {
+        let reminder = Reminder::for_tomorrow(&calendar(), date(1)).unwrap();
+        let subscription = |url: &str| WebhookSubscription {
+            id: fixtures::ID.to_string(),
+            url: url.to_string(),
+            format: WebhookFormat::Slack,
+            whimsy: true,
+        };
+
+        let slack = Notifier::sent_key(&subscription("https://hooks.slack.com/a"), &reminder);
+        let other = Notifier::sent_key(&subscription("https://hooks.slack.com/b"), &reminder);
+        let slack = slack.unwrap();
+        assert!(slack.starts_with("sent/a4Ot0000001E8i4EAC:2023-01-02/"));
+        assert!(!slack.contains("slack.com"));
+        assert_ne!(slack, other.unwrap());
+    }
+
+    #[test]
+    fn parse_subscriptions() {
+        let config = r#"[{"id": "foo", "url": "http://localhost/hook", "format": "slack"}, {"id": "bar", "url": "http://localhost/other"}]"#;
+        let subscriptions: Vec<WebhookSubscription> = serde_json::from_str(config).unwrap();
+
+        assert_eq!(subscriptions[0].format, WebhookFormat::Slack);
+        assert_eq!(subscriptions[1].format, WebhookFormat::Json);
+        assert!(subscriptions[1].whimsy);
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::BTreeSet;
+
+use chrono::Weekday;
+use chrono_tz::Tz;
+use chrono_tz::US::Pacific;
+use itertools::Itertools;
+use lambda_http::aws_lambda_events::query_map::QueryMap;
+use strum::{Display, EnumString, IntoEnumIterator};
+use url::form_urlencoded;
+
+use crate::error::Error;
+use crate::geo::Coordinates;
+use crate::pickup::PickupType;
+use crate::pickup_calendar::PickupCalendar;
+
+/// Every query parameter that changes what we render, in canonical order
+pub const OPTION_NAMES: [&str; 8] = [
+    "lang",
+    "lat",
+    "lon",
+    "naming",
+    "reminders",
+    "timezone",
+    "types",
+    "whimsy",
+];
+
+/// Enough for "the night before" and "the morning of" and then some, without letting a URL
+/// ask for thousands of alarms per event
+pub const MAX_REMINDERS: usize = 5;
+
+/// A week's worth of minutes
+pub const MAX_REMINDER_MINUTES: u32 = 7 * 24 * 60;
+
+/// The language pickup names are written in
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    En,
+    Es,
+}
+
+impl Lang {
+    pub fn weekday(&self, weekday: Weekday) -> &'static str {
+        let (en, es) = match weekday {
+            Weekday::Mon => ("Monday", "lunes"),
+            Weekday::Tue => ("Tuesday", "martes"),
+            Weekday::Wed => ("Wednesday", "miércoles"),
+            Weekday::Thu => ("Thursday", "jueves"),
+            Weekday::Fri => ("Friday", "viernes"),
+            Weekday::Sat => ("Saturday", "sábado"),
+            Weekday::Sun => ("Sunday", "domingo"),
+        };
+        match self {
+            Lang::En => en,
+            Lang::Es => es,
+        }
+    }
+
+    /// How far off a day is, e.g. "tomorrow" or "in 3 days"
+    pub fn days_from_now(&self, days: u64) -> String {
+        match (self, days) {
+            (Lang::En, 0) => "today".to_string(),
+            (Lang::En, 1) => "tomorrow".to_string(),
+            (Lang::En, n) => format!("in {n} days"),
+            (Lang::Es, 0) => "hoy".to_string(),
+            (Lang::Es, 1) => "mañana".to_string(),
+            (Lang::Es, n) => format!("en {n} días"),
+        }
+    }
+}
+
+/// What to call each calendar event
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Naming {
+    /// Just the pickup, e.g. "Trash"
+    #[default]
+    Type,
+    /// The pickup and where it is, for people with more than one calendar
+    Address,
+}
+
+/// Everything about a calendar that the person asking for it gets to pick
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RenderOptions {
+    pub whimsy: bool,
+    /// Only these kinds of pickups, or all of them when empty
+    pub types: BTreeSet<PickupType>,
+    pub lang: Lang,
+    /// Minutes before each pickup to remind (iCal only)
+    pub reminders: BTreeSet<u32>,
+    pub naming: Naming,
+    pub timezone: Tz,
+    /// Where the pickups happen, for iCal's GEO (only ever from `?lat=&lon=`)
+    pub coordinates: Option<Coordinates>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            whimsy: true,
+            types: BTreeSet::new(),
+            lang: Lang::default(),
+            reminders: BTreeSet::new(),
+            naming: Naming::default(),
+            timezone: Pacific,
+            coordinates: None,
+        }
+    }
+}
+
+fn bad(name: &str, value: &str) -> Error {
+    Error::BadOption {
+        name: name.to_string(),
+        value: value.to_string(),
+    }
+}
+
+fn parse_type(value: &str) -> Option<PickupType> {
+    PickupType::iter().find(|t| t.display_string(false).eq_ignore_ascii_case(value))
+}
+
+fn parse_reminder(value: &str) -> Option<u32> {
+    value
+        .parse()
+        .ok()
+        .filter(|minutes| *minutes <= MAX_REMINDER_MINUTES)
+}
+
+impl RenderOptions {
+    /// The defaults, give or take the emoji
+    pub fn with_whimsy(whimsy: bool) -> RenderOptions {
+        RenderOptions {
+            whimsy,
+            ..RenderOptions::default()
+        }
+    }
+
+    pub fn from_query(query: &QueryMap) -> Result<RenderOptions, Error> {
+        let mut options = RenderOptions::default();
+
+        // anything but "false" has always meant whimsy, so there's no such thing as a bad value
+        if let Some(value) = query.first("whimsy") {
+            options.whimsy = value != "false";
+        }
+
+        if let Some(value) = query.first("types") {
+            options.types = value
+                .split(',')
+                .map(|t| parse_type(t).ok_or_else(|| bad("types", value)))
+                .collect::<Result<_, _>>()?;
+
+            // asking for everything is the same as not asking
+            if options.types.len() == PickupType::iter().count() {
+                options.types.clear();
+            }
+        }
+
+        if let Some(value) = query.first("lang") {
+            options.lang = value.parse().map_err(|_| bad("lang", value))?;
+        }
+
+        if let Some(value) = query.first("reminders") {
+            options.reminders = value
+                .split(',')
+                .map(|m| parse_reminder(m).ok_or_else(|| bad("reminders", value)))
+                .collect::<Result<_, _>>()?;
+
+            if options.reminders.len() > MAX_REMINDERS {
+                return Err(bad("reminders", value));
+            }
+        }
+
+        if let Some(value) = query.first("naming") {
+            options.naming = value.parse().map_err(|_| bad("naming", value))?;
+        }
+
+        if let Some(value) = query.first("timezone") {
+            options.timezone = value.parse().map_err(|_| bad("timezone", value))?;
+        }
+
+        // half a spot on the map is no spot at all
+        match (query.first("lat"), query.first("lon")) {
+            (None, None) => {}
+            (Some(lat), Some(lon)) => {
+                if Coordinates::parse(lat, "0").is_none() {
+                    return Err(bad("lat", lat));
+                }
+                let coordinates = Coordinates::parse(lat, lon).ok_or_else(|| bad("lon", lon))?;
+                options.coordinates = Some(coordinates);
+            }
+            (Some(_), None) => return Err(bad("lon", "")),
+            (None, Some(_)) => return Err(bad("lat", "")),
+        }
+
+        Ok(options)
+    }
+
+    /// The options that differ from the defaults, in `OPTION_NAMES` order with their values
+    /// sorted, so every way of asking for the same calendar ends up at the same URL
+    pub fn canonical_query(&self) -> Vec<(&'static str, String)> {
+        let default = RenderOptions::default();
+        let mut query = vec![];
+
+        if self.lang != default.lang {
+            query.push(("lang", self.lang.to_string()));
+        }
+        if let Some(coordinates) = self.coordinates {
+            query.push(("lat", coordinates.latitude().to_string()));
+            query.push(("lon", coordinates.longitude().to_string()));
+        }
+        if self.naming != default.naming {
+            query.push(("naming", self.naming.to_string()));
+        }
+        if !self.reminders.is_empty() {
+            query.push(("reminders", self.reminders.iter().join(",")));
+        }
+        if self.timezone != default.timezone {
+            query.push(("timezone", self.timezone.name().to_string()));
+        }
+        if !self.types.is_empty() {
+            let types = self
+                .types
+                .iter()
+                .map(|t| t.display_string(false).to_lowercase())
+                .join(",");
+            query.push(("types", types));
+        }
+        if self.whimsy != default.whimsy {
+            query.push(("whimsy", self.whimsy.to_string()));
+        }
+
+        query
+    }
+
+    /// The canonical query as a string, encoded except for the commas between list values, which
+    /// are fine as-is and a lot easier to read
+    pub fn to_query(&self) -> String {
+        form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(self.canonical_query())
+            .finish()
+            .replace("%2C", ",")
+    }
+
+    /// Drops the pickups nobody asked for
+    pub fn filter(&self, mut calendar: PickupCalendar) -> PickupCalendar {
+        if !self.types.is_empty() {
+            calendar.pickups.retain(|p| self.types.contains(&p.name));
+        }
+        calendar
+    }
+
+    pub fn pickup_name(&self, pickup_type: PickupType) -> String {
+        let name = pickup_type.name_in(self.lang);
+        if self.whimsy {
+            format!("{} {name}", pickup_type.emoji())
+        } else {
+            name.to_string()
+        }
+    }
+
+    pub fn event_name(&self, pickup_type: PickupType, address: &str) -> String {
+        let name = self.pickup_name(pickup_type);
+        match self.naming {
+            Naming::Type => name,
+            Naming::Address => format!("{name} ({address})"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use chrono_tz::US::Eastern;
+    use lambda_http::aws_lambda_events::query_map::QueryMap;
+    use url::form_urlencoded;
+
+    use super::{Lang, Naming, RenderOptions, OPTION_NAMES};
+    use crate::error::Error;
+    use crate::geo::Coordinates;
+    use crate::pickup::PickupType;
+
+    fn parse(pairs: &[(&str, &str)]) -> Result<RenderOptions, Error> {
+        let query: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        RenderOptions::from_query(&QueryMap::from(query))
+    }
+
+    #[test]
+    fn defaults() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options, RenderOptions::default());
+        assert_eq!(options.to_query(), "");
+    }
+
+    #[test]
+    fn parses_everything() {
+        let options = parse(&[
+            ("whimsy", "false"),
+            ("types", "Trash,recyclables"),
+            ("lang", "es"),
+            ("reminders", "720,60,60"),
+            ("naming", "address"),
+            ("timezone", "US/Eastern"),
+            ("lat", "32.7987650"),
+            ("lon", "-117.2"),
+        ])
+        .unwrap();
+
+        assert!(!options.whimsy);
+        assert_eq!(
+            options.types.iter().copied().collect::<Vec<_>>(),
+            vec![PickupType::Recyclables, PickupType::Trash]
+        );
+        assert_eq!(options.lang, Lang::Es);
+        assert_eq!(
+            options.reminders.iter().copied().collect::<Vec<_>>(),
+            vec![60, 720]
+        );
+        assert_eq!(options.naming, Naming::Address);
+        assert_eq!(options.timezone, Eastern);
+        assert_eq!(options.coordinates, Coordinates::new(32.798765, -117.2));
+        assert_eq!(
+            options
+                .canonical_query()
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>(),
+            OPTION_NAMES
+        );
+        assert_eq!(
+            options.to_query(),
+            "lang=es&lat=32.798765&lon=-117.2&naming=address&reminders=60,720&timezone=US%2FEastern&types=recyclables,trash&whimsy=false"
+        );
+    }
+
+    #[test]
+    fn query_round_trips() {
+        let options = parse(&[("timezone", "Etc/GMT+5"), ("reminders", "60,720")]).unwrap();
+        let query = options.to_query();
+        assert_eq!(query, "reminders=60,720&timezone=Etc%2FGMT%2B5");
+
+        let pairs = form_urlencoded::parse(query.as_bytes())
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(
+            RenderOptions::from_query(&QueryMap::from(pairs)).unwrap(),
+            options
+        );
+    }
+
+    #[test]
+    fn whimsy_unless_false() {
+        for value in ["true", "0", "no", "False", "nah", ""] {
+            assert!(parse(&[("whimsy", value)]).unwrap().whimsy, "{value}");
+        }
+        assert!(!parse(&[("whimsy", "false")]).unwrap().whimsy);
+    }
+
+    #[test]
+    fn all_types_is_no_filter() {
+        let options = parse(&[("types", "trash,organics,recyclables")]).unwrap();
+        assert!(options.types.is_empty());
+    }
+
+    #[test]
+    fn bad_values() {
+        for (name, value) in [
+            ("types", "trash,compost"),
+            ("types", ""),
+            ("lang", "fr"),
+            ("reminders", "-5"),
+            ("reminders", "99999"),
+            ("reminders", "1,2,3,4,5,6"),
+            ("naming", "street"),
+            ("timezone", "Mars/Olympus_Mons"),
+        ] {
+            assert!(
+                matches!(
+                    parse(&[(name, value)]),
+                    Err(Error::BadOption { name: n, value: v }) if n == name && v == value
+                ),
+                "{name}={value}"
+            );
+        }
+    }
+
+    #[test]
+    fn bad_coordinates() {
+        for (lat, lon, name, value) in [
+            ("91", "0", "lat", "91"),
+            ("north", "0", "lat", "north"),
+            ("32.8", "-181", "lon", "-181"),
+            ("32.8", "", "lon", ""),
+        ] {
+            assert!(
+                matches!(
+                    parse(&[("lat", lat), ("lon", lon)]),
+                    Err(Error::BadOption { name: n, value: v }) if n == name && v == value
+                ),
+                "lat={lat}&lon={lon}"
+            );
+        }
+
+        // one without the other
+        assert!(matches!(
+            parse(&[("lat", "32.8")]),
+            Err(Error::BadOption { name, .. }) if name == "lon"
+        ));
+        assert!(matches!(
+            parse(&[("lon", "-117.2")]),
+            Err(Error::BadOption { name, .. }) if name == "lat"
+        ));
+    }
+
+    #[test]
+    fn names() {
+        let options = RenderOptions {
+            lang: Lang::Es,
+            naming: Naming::Address,
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            options.event_name(PickupType::Trash, "1234 AGATE ST"),
+            "🗑️ Basura (1234 AGATE ST)"
+        );
+        assert_eq!(
+            RenderOptions::default().pickup_name(PickupType::Recyclables),
+            PickupType::Recyclables.display_string(true)
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use std::sync::LazyLock;
+
+use scraper::{Html, Selector};
+
+use crate::error::Error;
+use crate::pickup_calendar::{ADDRESS_SELECTOR, SCHEDULE_SELECTOR};
+
+/// What the city actually sent back when we asked for a collection detail page
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum PageKind {
+    /// The page we want: an address and a schedule
+    CollectionDetail,
+    /// The city didn't recognize the ID and is bouncing us to the address lookup
+    LookupLanding,
+    /// Salesforce is down for maintenance
+    Maintenance,
+    /// Salesforce wants us to log in, which we never should have to do
+    LoginWall,
+    /// Who knows
+    Unknown,
+}
+
+static SCRIPT_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("script").unwrap());
+static TITLE_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("title").unwrap());
+static LOGIN_SELECTOR: LazyLock<Selector> = LazyLock::new(|| {
+    Selector::parse("form#login_form, input[name=pw], input[type=password]").unwrap()
+});
+static BODY_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("body").unwrap());
+static ONLOAD_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("[onload]").unwrap());
+
+fn text_of(document: &Html, selector: &Selector) -> String {
+    document
+        .select(selector)
+        .flat_map(|e| e.text())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+impl PageKind {
+    pub fn classify(document: &Html) -> PageKind {
+        let has = |selector: &Selector| document.select(selector).next().is_some();
+        if has(&ADDRESS_SELECTOR) && has(&SCHEDULE_SELECTOR) {
+            return PageKind::CollectionDetail;
+        }
+
+        // the redirect lives in script, either a <script> or an onload handler
+        let scripts = text_of(document, &SCRIPT_SELECTOR)
+            + &document
+                .select(&ONLOAD_SELECTOR)
+                .filter_map(|e| e.value().attr("onload"))
+                .collect::<String>()
+                .to_lowercase();
+        if scripts.contains("handleredirect") || scripts.contains("collectionmaplookup") {
+            return PageKind::LookupLanding;
+        }
+
+        if has(&LOGIN_SELECTOR) {
+            return PageKind::LoginWall;
+        }
+
+        let title = text_of(document, &TITLE_SELECTOR);
+        let body = text_of(document, &BODY_SELECTOR);
+        if title.contains("maintenance") || body.contains("down for maintenance") {
+            return PageKind::Maintenance;
+        }
+
+        PageKind::Unknown
+    }
+
+    /// Anything but a collection detail page is an error, and each one is its own kind of error
+    pub fn ensure_collection_detail(self, id: &str) -> Result<(), Error> {
+        match self {
+            PageKind::CollectionDetail => Ok(()),
+            PageKind::LookupLanding => Err(Error::RedirectPage(id.to_string())),
+            PageKind::Maintenance => Err(Error::MaintenancePage),
+            PageKind::LoginWall => Err(Error::LoginWall),
+            PageKind::Unknown => Err(Error::UnrecognizedPage),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use scraper::Html;
+
+    use super::PageKind;
+    use crate::error::Error;
+
+    fn classify(html: &str) -> PageKind {
+        PageKind::classify(&Html::parse_document(html))
+    }
+
+    #[test]
+    fn collection_detail() {
+        let page = include_str!("../tests/data/pages/collection_detail.html");
+        assert_eq!(classify(page), PageKind::CollectionDetail);
+        assert!(classify(page).ensure_collection_detail("foo").is_ok());
+    }
+
+    #[test]
+    fn lookup_landing() {
+        let page = include_str!("../tests/data/pages/lookup_landing.html");
+        assert_eq!(classify(page), PageKind::LookupLanding);
+        assert!(matches!(
+            classify(page).ensure_collection_detail("foo"),
+            Err(Error::RedirectPage(id)) if id == "foo"
+        ));
+    }
+
+    #[test]
+    fn maintenance() {
+        let page = include_str!("../tests/data/pages/maintenance.html");
+        assert_eq!(classify(page), PageKind::Maintenance);
+        assert!(matches!(
+            classify(page).ensure_collection_detail("foo"),
+            Err(Error::MaintenancePage)
+        ));
+    }
+
+    #[test]
+    fn login_wall() {
+        let page = include_str!("../tests/data/pages/login.html");
+        assert_eq!(classify(page), PageKind::LoginWall);
+        assert!(matches!(
+            classify(page).ensure_collection_detail("foo"),
+            Err(Error::LoginWall)
+        ));
+    }
+
+    #[test]
+    fn unknown() {
+        let page = include_str!("../tests/data/pages/unknown.html");
+        assert_eq!(classify(page), PageKind::Unknown);
+        assert!(matches!(
+            classify(page).ensure_collection_detail("foo"),
+            Err(Error::UnrecognizedPage)
+        ));
+    }
+
+    #[test]
+    fn onload_redirect() {
+        let page = "<html><body onload=\"handleRedirect()\"></body></html>";
+        assert_eq!(classify(page), PageKind::LookupLanding);
+    }
+
+    #[test]
+    fn mentioning_redirects_in_text_is_not_a_redirect() {
+        let page = "<html><body><p>handleRedirect</p></body></html>";
+        assert_eq!(classify(page), PageKind::Unknown);
+    }
+}
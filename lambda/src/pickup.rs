@@ -1,13 +1,25 @@
 use std::sync::LazyLock;
 
 use crate::error::Error;
+use crate::options::Lang;
 use chrono::NaiveDate;
 use scraper::{ElementRef, Html, Selector};
 use serde::{Deserialize, Serialize};
-use strum::{Display, EnumString};
+use strum::{Display, EnumIter, EnumString};
 
 #[derive(
-    EnumString, Display, Serialize, Deserialize, Debug, Eq, PartialEq, PartialOrd, Ord, Clone, Copy,
+    EnumString,
+    EnumIter,
+    Display,
+    Serialize,
+    Deserialize,
+    Debug,
+    Eq,
+    PartialEq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
 )]
 pub enum PickupType {
     #[serde(rename(serialize = "♻️ Recyclables", deserialize = "♻️ Recyclables"))]
@@ -24,6 +36,25 @@ pub enum PickupType {
 }
 
 impl PickupType {
+    pub fn emoji(&self) -> &'static str {
+        match self {
+            PickupType::Recyclables => "♻️",
+            PickupType::Organics => "🌳",
+            PickupType::Trash => "🗑️",
+        }
+    }
+
+    pub fn name_in(&self, lang: Lang) -> &'static str {
+        match (self, lang) {
+            (PickupType::Recyclables, Lang::En) => "Recyclables",
+            (PickupType::Organics, Lang::En) => "Organics",
+            (PickupType::Trash, Lang::En) => "Trash",
+            (PickupType::Recyclables, Lang::Es) => "Reciclables",
+            (PickupType::Organics, Lang::Es) => "Orgánicos",
+            (PickupType::Trash, Lang::Es) => "Basura",
+        }
+    }
+
     pub fn display_string(&self, whimsy: bool) -> String {
         if whimsy {
             self.to_string()
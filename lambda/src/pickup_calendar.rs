@@ -1,16 +1,21 @@
+use crate::address::Address;
+use crate::base_url::BaseUrl;
 use crate::error::Error;
+use crate::options::RenderOptions;
 use crate::pickup::nth_text;
 use crate::pickup::{Pickup, PickupType};
-use chrono::{DateTime, Days, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use crate::redact;
+use chrono::{DateTime, Days, Duration, NaiveDateTime, NaiveTime, TimeZone, Utc};
 use chrono_tz::Tz;
 use chrono_tz::US::Pacific;
-use icalendar::{Calendar, Component, Event, EventLike};
+use icalendar::{Alarm, Calendar, Component, Event, EventLike};
 use itertools::Itertools;
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
 use std::sync::LazyLock;
+use tracing::{debug, field, instrument, Span};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub struct PickupCalendar {
     pub id: String,
     pub address: String,
@@ -35,6 +40,7 @@ impl PickupCalendar {
                     ]);
                 }
 
+                #[allow(clippy::unnecessary_sort_by)]
                 pickups.sort_by(|x, y| x.name.cmp(&y.name));
                 pickups
             })
@@ -61,15 +67,24 @@ impl PickupCalendar {
         })
     }
 
+    /// The address in pieces, tidied up for people
+    pub fn parsed_address(&self) -> Address {
+        Address::parse(&self.address)
+    }
+
     pub fn expires_header(&self) -> String {
-        match self.valid_until() {
-            Some(d) => internet_message_format(&d).to_string(),
-            None => "0".to_string(), // don't cache bad responses
-        }
+        expires_header(self.valid_until())
+    }
+}
+
+pub(crate) fn expires_header(valid_until: Option<DateTime<Utc>>) -> String {
+    match valid_until {
+        Some(d) => internet_message_format(&d).to_string(),
+        None => "0".to_string(), // don't cache bad responses
     }
 }
 
-fn internet_message_format(d: &DateTime<Utc>) -> String {
+pub(crate) fn internet_message_format(d: &DateTime<Utc>) -> String {
     d.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
 }
 fn naive_to_pacific(naive: &NaiveDateTime) -> Option<DateTime<Tz>> {
@@ -81,8 +96,12 @@ fn naive_to_pacific(naive: &NaiveDateTime) -> Option<DateTime<Tz>> {
 }
 
 impl PickupCalendar {
-    pub fn to_calendar(&self, whimsy: bool) -> Result<Calendar, Error> {
-        let url = format!("https://stabbylambda.com/trashcal/{}", self.id);
+    pub fn to_calendar(
+        &self,
+        base_url: &BaseUrl,
+        options: &RenderOptions,
+    ) -> Result<Calendar, Error> {
+        let url = base_url.calendar_url(&self.id);
         let description = format!(
             "Trashcal: {url}
 
@@ -90,17 +109,45 @@ SD Trash Page: https://getitdone.sandiego.gov/CollectionDetail?id={}",
             self.id
         );
 
+        let address = self.parsed_address();
+        let location = address.to_string();
+        let street = address.street_line();
+        // the street tells calendars for more than one address apart in a calendar app's list
+        let name = match street.as_str() {
+            "" => "Trashcal".to_string(),
+            street => format!("Trashcal – {street}"),
+        };
+        // the city's page doesn't say where the address is, so only if somebody told us
+        let geo = options.coordinates.map(|c| c.to_ical());
+
         // Create new calendar events and add them
         let events = self.pickups.iter().map(|pickup| {
-            Event::new()
+            let summary = options.event_name(pickup.name, &street);
+            let mut event = Event::new();
+            event
                 .all_day(pickup.date)
                 .url(&url)
-                .summary(&pickup.name.display_string(whimsy))
+                .summary(&summary)
                 .description(&description)
-                .done()
+                .location(&location);
+            if let Some(geo) = &geo {
+                event.add_property("GEO", geo);
+            }
+
+            // all day events start at midnight, so a reminder of 360 is 6PM the night before
+            for minutes in &options.reminders {
+                event.alarm(Alarm::display(
+                    &summary,
+                    -Duration::minutes(i64::from(*minutes)),
+                ));
+            }
+            event.done()
         });
 
-        let mut calendar = Calendar::new().name("Trashcal").done();
+        let mut calendar = Calendar::new()
+            .name(&name)
+            .timezone(options.timezone.name())
+            .done();
         calendar.extend(events);
         Ok(calendar)
     }
@@ -110,28 +157,39 @@ impl TryFrom<PickupCalendar> for Calendar {
     type Error = Error;
 
     fn try_from(value: PickupCalendar) -> Result<Self, Self::Error> {
-        // Default to whimsy = true for backward compatibility
-        value.to_calendar(true)
+        // Default to the public trashcal and whimsy = true for backward compatibility
+        value.to_calendar(&BaseUrl::default(), &RenderOptions::default())
     }
 }
 
-static ADDRESS_SELECTOR: LazyLock<Selector> =
+pub(crate) static ADDRESS_SELECTOR: LazyLock<Selector> =
     LazyLock::new(|| Selector::parse("p.subheading").unwrap());
-static SCHEDULE_SELECTOR: LazyLock<Selector> =
+pub(crate) static SCHEDULE_SELECTOR: LazyLock<Selector> =
     LazyLock::new(|| Selector::parse("div.schedule div").unwrap());
 
 impl<'a> TryFrom<(&'a str, &'a Html)> for PickupCalendar {
     type Error = Error;
 
+    #[instrument(
+        name = "parse",
+        skip_all,
+        fields(collection_id = %redact::id(id), blocks = field::Empty, skipped = field::Empty)
+    )]
     fn try_from((id, document): (&'a str, &'a Html)) -> Result<Self, Self::Error> {
         let address = nth_text(document.root_element(), &ADDRESS_SELECTOR, 0)?;
         let schedule = document.select(&SCHEDULE_SELECTOR);
-        let pickups: Result<Vec<Pickup>, Error> = schedule
-            .map(Pickup::try_from)
-            .filter(Result::is_ok)
-            .collect();
+        let (pickups, skipped): (Vec<Pickup>, Vec<Error>) =
+            schedule.map(Pickup::try_from).partition_result();
 
-        Ok(PickupCalendar::new(id, address, pickups?))
+        // the schedule has blocks that aren't pickups, so skipping some is normal
+        for e in &skipped {
+            debug!(message = "Skipped schedule block", error = %e);
+        }
+        let span = Span::current();
+        span.record("blocks", pickups.len() + skipped.len());
+        span.record("skipped", skipped.len());
+
+        Ok(PickupCalendar::new(id, address, pickups))
     }
 }
 
@@ -193,6 +251,16 @@ mod test {
         }
     }
 
+    #[test]
+    fn parse_collection_detail_page() {
+        let html = include_str!("../tests/data/pages/collection_detail.html");
+        let document = scraper::Html::parse_document(html);
+
+        let actual = PickupCalendar::try_from(("foo", &document)).unwrap();
+        assert_eq!(actual.address, "1234 AGATE ST, San Diego, CA 92109");
+        assert_eq!(actual.pickups.len(), 5);
+    }
+
     #[test]
     fn insert_pickup_for_opposite_week_recycling() {
         let this_week = Utc::now().date_naive();
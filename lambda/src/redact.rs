@@ -0,0 +1,238 @@
+//! What we're willing to write to the logs. Every collection ID is somebody's home, and
+//! CloudWatch keeps everything for a month, so by default IDs are hashed and addresses cut down
+//! to something that can't find a house.
+
+use std::sync::LazyLock;
+
+use itertools::Itertools;
+use strum::EnumString;
+use url::Url;
+
+use crate::address::Address;
+use crate::collection_id::CollectionId;
+use crate::options::RenderOptions;
+
+/// `off`, `street` or `zip`
+pub const REDACTION_VAR: &str = "TRASHCAL_LOG_REDACTION";
+
+/// What's left of an address that had nothing worth keeping
+const REDACTED: &str = "[redacted]";
+
+/// How much of an address makes it into the logs
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, EnumString)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum Redaction {
+    /// Everything as-is, for debugging locally
+    Off,
+    /// The street name and ZIP, without the house number or unit
+    #[default]
+    Street,
+    /// Just the ZIP
+    Zip,
+}
+
+static REDACTION: LazyLock<Redaction> = LazyLock::new(Redaction::from_env);
+
+/// FNV-1a, which is stable across builds and platforms (unlike `DefaultHasher`), so the same ID
+/// always shows up as the same hash when searching the logs
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl Redaction {
+    pub fn from_env() -> Redaction {
+        std::env::var(REDACTION_VAR)
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn id(&self, id: &str) -> String {
+        if *self == Redaction::Off {
+            return id.to_string();
+        }
+
+        // "a4Ot0000001E8i4", "a4Ot0000001E8i4eac.ics" and friends all hash the same
+        let normalized = id.parse::<CollectionId>();
+        let id = normalized.as_ref().map(CollectionId::as_str).unwrap_or(id);
+        format!("{:016x}", fnv1a(id))
+    }
+
+    pub fn address(&self, address: &str) -> String {
+        let parsed = Address::parse(address);
+        // the +4 narrows it down to a few houses, so it goes too
+        let zip = parsed.zip.as_deref().and_then(|zip| zip.split('-').next());
+        let street = Some(parsed.street.as_str()).filter(|s| !s.is_empty());
+
+        let kept = match self {
+            Redaction::Off => return address.to_string(),
+            Redaction::Street => [street, zip].into_iter().flatten().join(", "),
+            Redaction::Zip => zip.unwrap_or_default().to_string(),
+        };
+        if kept.is_empty() {
+            REDACTED.to_string()
+        } else {
+            kept
+        }
+    }
+
+    /// Swaps every mention of `id` in free text (like an error with a URL in it) for its hash
+    pub fn text(&self, text: &str, id: &str) -> String {
+        if *self == Redaction::Off || id.is_empty() {
+            return text.to_string();
+        }
+        text.replace(id, &self.id(id))
+    }
+
+    /// A URL with its `id` parameter hashed
+    pub fn url(&self, url: &str) -> String {
+        let Ok(mut parsed) = Url::parse(url) else {
+            return url.to_string();
+        };
+        if *self == Redaction::Off {
+            return parsed.to_string();
+        }
+
+        let pairs = parsed
+            .query_pairs()
+            .map(|(name, value)| match name.as_ref() {
+                "id" => (name.to_string(), self.id(&value)),
+                _ => (name.to_string(), value.to_string()),
+            })
+            .collect::<Vec<_>>();
+        if !pairs.is_empty() {
+            parsed.query_pairs_mut().clear().extend_pairs(pairs);
+        }
+        parsed.to_string()
+    }
+
+    /// The canonical query for `options`, minus where somebody said they live
+    pub fn options(&self, options: &RenderOptions) -> String {
+        if *self == Redaction::Off || options.coordinates.is_none() {
+            return options.to_query();
+        }
+        let options = RenderOptions {
+            coordinates: None,
+            ..options.clone()
+        };
+        options.to_query()
+    }
+
+    pub fn enabled(&self) -> bool {
+        *self != Redaction::Off
+    }
+}
+
+/// A collection ID as it should appear in the logs
+pub fn id(id: &str) -> String {
+    REDACTION.id(id)
+}
+
+/// An address as it should appear in the logs
+pub fn address(address: &str) -> String {
+    REDACTION.address(address)
+}
+
+/// Free text with `id` hashed wherever it appears
+pub fn text(text: &str, id: &str) -> String {
+    REDACTION.text(text, id)
+}
+
+/// A URL with its `id` parameter hashed
+pub fn url(url: &str) -> String {
+    REDACTION.url(url)
+}
+
+/// Render options as they should appear in the logs
+pub fn options(options: &RenderOptions) -> String {
+    REDACTION.options(options)
+}
+
+pub fn enabled() -> bool {
+    REDACTION.enabled()
+}
+
+#[cfg(test)]
+mod test {
+    use super::Redaction;
+    use crate::geo::Coordinates;
+    use crate::options::RenderOptions;
+
+    const ADDRESS: &str = "1234 AGATE ST, San Diego, CA 92109";
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!("off".parse(), Ok(Redaction::Off));
+        assert_eq!("Street".parse(), Ok(Redaction::Street));
+        assert_eq!("ZIP".parse(), Ok(Redaction::Zip));
+        assert!("everything".parse::<Redaction>().is_err());
+    }
+
+    #[test]
+    fn hashes_ids() {
+        let id = Redaction::Street.id("a4Ot0000001E8i4EAC");
+        assert_eq!(id.len(), 16);
+        assert!(!id.contains("a4Ot"));
+        assert_eq!(Redaction::Zip.id("a4Ot0000001E8i4EAC"), id);
+        // the 15 character form and an extension don't change the hash
+        assert_eq!(Redaction::Street.id("a4Ot0000001E8i4"), id);
+        assert_eq!(Redaction::Street.id("a4Ot0000001E8i4EAC.json"), id);
+        assert_eq!(Redaction::Street.id("a4Ot0000001E8i4eac.ics"), id);
+        assert_ne!(Redaction::Street.id("a4Ot0000001E8iAEAC"), id);
+        assert_eq!(
+            Redaction::Off.id("a4Ot0000001E8i4EAC"),
+            "a4Ot0000001E8i4EAC"
+        );
+    }
+
+    #[test]
+    fn truncates_addresses() {
+        assert_eq!(Redaction::Street.address(ADDRESS), "Agate St, 92109");
+        assert_eq!(Redaction::Zip.address(ADDRESS), "92109");
+        assert_eq!(Redaction::Off.address(ADDRESS), ADDRESS);
+        assert_eq!(
+            Redaction::Street.address("1234-1/2 30TH ST APT 5, San Diego, CA 92104-1234"),
+            "30th St, 92104"
+        );
+        assert_eq!(Redaction::Street.address("1234 AGATE ST #5"), "Agate St");
+        assert_eq!(Redaction::Zip.address("1234 AGATE ST"), "[redacted]");
+        assert_eq!(Redaction::Street.address("1234"), "[redacted]");
+    }
+
+    #[test]
+    fn hashes_url_ids() {
+        let url = "https://getitdone.sandiego.gov/CollectionDetail?id=a4Ot0000001E8i4EAC";
+        assert_eq!(
+            Redaction::Street.url(url),
+            format!(
+                "https://getitdone.sandiego.gov/CollectionDetail?id={}",
+                Redaction::Street.id("a4Ot0000001E8i4EAC")
+            )
+        );
+        assert_eq!(Redaction::Off.url(url), url);
+    }
+
+    #[test]
+    fn drops_coordinates_from_options() {
+        let options = RenderOptions {
+            coordinates: Coordinates::new(32.798765, -117.2),
+            ..RenderOptions::with_whimsy(false)
+        };
+        assert_eq!(Redaction::Street.options(&options), "whimsy=false");
+        assert_eq!(
+            Redaction::Off.options(&options),
+            "lat=32.798765&lon=-117.2&whimsy=false"
+        );
+    }
+
+    #[test]
+    fn scrubs_text() {
+        let error = "error sending request for url (https://getitdone.sandiego.gov/CollectionDetail?id=a4Ot0000001E8i4EAC)";
+        let scrubbed = Redaction::Street.text(error, "a4Ot0000001E8i4EAC");
+        assert!(!scrubbed.contains("a4Ot0000001E8i4EAC"));
+        assert!(scrubbed.contains(&Redaction::Street.id("a4Ot0000001E8i4EAC")));
+        assert_eq!(Redaction::Off.text(error, "a4Ot0000001E8i4EAC"), error);
+    }
+}
@@ -0,0 +1,236 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::address::Address;
+use crate::base_url::BaseUrl;
+use crate::export::{CsvRenderer, TextRenderer};
+use crate::feed::{AtomRenderer, RssRenderer};
+use crate::format::OutputFormat;
+use crate::html::HtmlRenderer;
+use crate::options::RenderOptions;
+use crate::pickup_calendar::PickupCalendar;
+
+/// Turns a calendar into one of our output formats
+pub trait Renderer {
+    fn content_type(&self) -> &'static str;
+
+    /// The name to save it as, for formats that are meant to be downloaded or opened as a file
+    fn filename(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Whether browsers should show it rather than download it
+    fn inline(&self) -> bool {
+        false
+    }
+
+    fn render(
+        &self,
+        calendar: &PickupCalendar,
+        base_url: &BaseUrl,
+        options: &RenderOptions,
+    ) -> Result<String>;
+
+    /// When what we rendered goes stale, which is when the calendar does unless the format
+    /// changes sooner than that
+    fn valid_until(
+        &self,
+        calendar: &PickupCalendar,
+        _options: &RenderOptions,
+        _now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        calendar.valid_until()
+    }
+
+    fn content_disposition(&self) -> Option<String> {
+        let disposition = if self.inline() {
+            "inline"
+        } else {
+            "attachment"
+        };
+        self.filename()
+            .map(|filename| format!("{disposition}; filename={filename}"))
+    }
+}
+
+pub struct IcalRenderer;
+
+impl Renderer for IcalRenderer {
+    fn content_type(&self) -> &'static str {
+        "text/calendar;charset=UTF-8"
+    }
+
+    fn filename(&self) -> Option<&'static str> {
+        Some("trashcal.ics")
+    }
+
+    fn render(
+        &self,
+        calendar: &PickupCalendar,
+        base_url: &BaseUrl,
+        options: &RenderOptions,
+    ) -> Result<String> {
+        Ok(calendar.to_calendar(base_url, options)?.to_string())
+    }
+}
+
+pub struct JsonRenderer;
+
+/// The calendar as-is, plus its address in pieces
+#[derive(Serialize)]
+struct CalendarJson<'a> {
+    #[serde(flatten)]
+    calendar: &'a PickupCalendar,
+    address_parts: Address,
+}
+
+impl Renderer for JsonRenderer {
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn render(
+        &self,
+        calendar: &PickupCalendar,
+        _base_url: &BaseUrl,
+        _options: &RenderOptions,
+    ) -> Result<String> {
+        let json = CalendarJson {
+            calendar,
+            address_parts: calendar.parsed_address(),
+        };
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+}
+
+/// The renderer for each format
+pub fn renderer(format: OutputFormat) -> &'static dyn Renderer {
+    match format {
+        OutputFormat::ICal => &IcalRenderer,
+        OutputFormat::Json => &JsonRenderer,
+        OutputFormat::Html => &HtmlRenderer,
+        OutputFormat::Csv => &CsvRenderer,
+        OutputFormat::Text => &TextRenderer,
+        OutputFormat::Atom => &AtomRenderer,
+        OutputFormat::Rss => &RssRenderer,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use strum::IntoEnumIterator;
+
+    use super::renderer;
+    use crate::base_url::BaseUrl;
+    use crate::fixtures::{calendar, calendar_at};
+    use crate::format::OutputFormat;
+    use crate::geo::Coordinates;
+    use crate::options::{Naming, RenderOptions};
+    use crate::pickup::PickupType;
+    use crate::pickup_calendar::PickupCalendar;
+
+    #[test]
+    fn every_format_renders() {
+        let calendar = calendar(&[(PickupType::Trash, 2)]);
+        for format in OutputFormat::iter() {
+            let body = renderer(format)
+                .render(&calendar, &BaseUrl::default(), &RenderOptions::default())
+                .unwrap();
+            assert!(!body.is_empty(), "{format} rendered nothing");
+        }
+    }
+
+    #[test]
+    fn content_disposition() {
+        assert_eq!(
+            renderer(OutputFormat::ICal)
+                .content_disposition()
+                .as_deref(),
+            Some("attachment; filename=trashcal.ics")
+        );
+        assert_eq!(
+            renderer(OutputFormat::Text)
+                .content_disposition()
+                .as_deref(),
+            Some("inline; filename=trashcal.txt")
+        );
+        assert_eq!(renderer(OutputFormat::Json).content_disposition(), None);
+    }
+
+    #[test]
+    fn ical_locates_events() {
+        let calendar = calendar_at(
+            "1234 AGATE ST APT 5, San Diego, CA 92109",
+            &[(PickupType::Trash, 2)],
+        );
+        let options = RenderOptions {
+            naming: Naming::Address,
+            ..RenderOptions::with_whimsy(false)
+        };
+        let body = renderer(OutputFormat::ICal)
+            .render(&calendar, &BaseUrl::default(), &options)
+            .unwrap();
+        assert!(
+            body.contains("SUMMARY:Trash (1234 Agate St Apt 5)"),
+            "{body}"
+        );
+        assert!(
+            body.contains("LOCATION:1234 Agate St Apt 5\\, San Diego\\, CA 92109"),
+            "{body}"
+        );
+        assert!(
+            body.contains("X-WR-CALNAME:Trashcal – 1234 Agate St Apt 5"),
+            "{body}"
+        );
+    }
+
+    #[test]
+    fn ical_geo_from_asked_for_coordinates() {
+        let render = |calendar: &PickupCalendar, options: &RenderOptions| {
+            renderer(OutputFormat::ICal)
+                .render(calendar, &BaseUrl::default(), options)
+                .unwrap()
+        };
+
+        let calendar = calendar(&[(PickupType::Trash, 2)]);
+        assert!(!render(&calendar, &RenderOptions::default()).contains("GEO:"));
+
+        let options = RenderOptions {
+            coordinates: Coordinates::new(32.798765, -117.2),
+            ..RenderOptions::default()
+        };
+        assert!(render(&calendar, &options).contains("GEO:32.798765;-117.2"));
+    }
+
+    #[test]
+    fn json_has_address_parts() {
+        let calendar = calendar_at(
+            "1234 AGATE ST, San Diego, CA 92109",
+            &[(PickupType::Trash, 2)],
+        );
+        let body = renderer(OutputFormat::Json)
+            .render(&calendar, &BaseUrl::default(), &RenderOptions::default())
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["address"], "1234 AGATE ST, San Diego, CA 92109");
+        assert_eq!(json["address_parts"]["street"], "Agate St");
+        assert_eq!(json["address_parts"]["city"], "San Diego");
+        assert_eq!(json["address_parts"]["zip"], "92109");
+        assert_eq!(json["pickups"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn ical_honors_whimsy() {
+        let options = RenderOptions::with_whimsy(false);
+        let body = renderer(OutputFormat::ICal)
+            .render(
+                &calendar(&[(PickupType::Trash, 2)]),
+                &BaseUrl::default(),
+                &options,
+            )
+            .unwrap();
+        assert!(body.contains("SUMMARY:Trash"));
+        assert!(!body.contains("🗑️"));
+    }
+}
@@ -0,0 +1,305 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use http::StatusCode;
+use lambda_runtime::tracing::warn;
+use reqwest::{Client, Response};
+
+use crate::error::Error;
+
+/// Statuses that usually mean Salesforce is having a moment rather than that we did something wrong.
+/// A 429 isn't one of them: the city said how long to back off, and that's longer than we can wait.
+fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+fn is_transient_error(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout() || e.is_request()
+}
+
+/// Bounded retries with jittered exponential backoff
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// How long all the attempts get together, backoff included
+    pub deadline: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(2),
+            // well under the 25s Lambda timeout, so there's time left to serve a stale calendar
+            deadline: Duration::from_secs(15),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// "Full jitter": anywhere between nothing and the exponential backoff for this attempt
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        backoff.mul_f64(fastrand::f64())
+    }
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// Stops hammering the city after repeated failures, letting a request through again after a cooldown
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker::new(5, Duration::from_secs(30))
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            failure_threshold,
+            cooldown,
+            state: Default::default(),
+        }
+    }
+
+    /// Whether a request should even be attempted
+    pub fn allow(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        match state.opened_at {
+            Some(opened_at) => opened_at.elapsed() >= self.cooldown,
+            None => true,
+        }
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= self.failure_threshold {
+            // (re)open, which also restarts the cooldown after a failed trial request
+            state.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+/// GETs a URL, retrying transient failures until the policy's attempts or deadline run out.
+/// Whatever comes back after the last attempt is returned as-is, so it's up to the caller to
+/// decide what a bad status means.
+pub async fn get_with_retry(
+    client: &Client,
+    url: &str,
+    policy: &RetryPolicy,
+    breaker: &CircuitBreaker,
+) -> Result<Response, Error> {
+    if !breaker.allow() {
+        return Err(Error::CircuitOpen);
+    }
+
+    let started = Instant::now();
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        // no attempt gets to run past the deadline, whatever the client's own timeout is
+        let remaining = policy.deadline.saturating_sub(started.elapsed());
+        let Ok(result) = tokio::time::timeout(remaining, client.get(url).send()).await else {
+            breaker.record_failure();
+            return Err(Error::Timeout);
+        };
+        let transient = match &result {
+            Ok(response) => is_transient_status(response.status()),
+            Err(e) => is_transient_error(e),
+        };
+
+        if !transient {
+            breaker.record_success();
+            return Ok(result?);
+        }
+
+        let delay = policy.delay(attempt - 1);
+        if attempt >= policy.max_attempts || started.elapsed() + delay >= policy.deadline {
+            breaker.record_failure();
+            return Ok(result?);
+        }
+
+        warn!(
+            message = "Retrying upstream request",
+            attempt,
+            delay_ms = delay.as_millis() as u64,
+            status = ?result.as_ref().ok().map(|r| r.status())
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use super::{get_with_retry, CircuitBreaker, RetryPolicy};
+    use crate::error::Error;
+    use crate::fixtures::{response, server, silent_server};
+
+    /// Answers 503 for the first `failures` requests and 200 after that
+    async fn flaky_server(failures: u32) -> (String, Arc<AtomicU32>) {
+        failing_server(failures, response("503 Service Unavailable", "nope")).await
+    }
+
+    /// Answers `failure` for the first `failures` requests and 200 after that
+    async fn failing_server(failures: u32, failure: String) -> (String, Arc<AtomicU32>) {
+        server(move |n| {
+            if n < failures {
+                failure.clone()
+            } else {
+                response("200 OK", "ok")
+            }
+        })
+        .await
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            deadline: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn delay_is_bounded() {
+        let policy = RetryPolicy::default();
+        for attempt in 0..20 {
+            assert!(policy.delay(attempt) <= policy.max_delay);
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_until_success() {
+        let (url, requests) = flaky_server(2).await;
+        let breaker = CircuitBreaker::default();
+
+        let response = get_with_retry(&reqwest::Client::new(), &url, &policy(), &breaker)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (url, requests) = flaky_server(10).await;
+        let breaker = CircuitBreaker::default();
+
+        let response = get_with_retry(&reqwest::Client::new(), &url, &policy(), &breaker)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 503);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_are_not_retried() {
+        let (url, requests) = failing_server(
+            10,
+            "HTTP/1.1 429 Too Many Requests\r\nretry-after: 120\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_string(),
+        )
+        .await;
+
+        let response = get_with_retry(
+            &reqwest::Client::new(),
+            &url,
+            &policy(),
+            &CircuitBreaker::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.status(), 429);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_at_the_deadline() {
+        let url = silent_server().await;
+
+        let policy = RetryPolicy {
+            deadline: Duration::from_millis(200),
+            ..policy()
+        };
+        let started = Instant::now();
+        let result = get_with_retry(
+            &reqwest::Client::new(),
+            &url,
+            &policy,
+            &CircuitBreaker::default(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(Error::Timeout)), "{result:?}");
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn breaker_opens_after_repeated_failures() {
+        let (url, requests) = flaky_server(10).await;
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        let client = reqwest::Client::new();
+
+        for _ in 0..2 {
+            get_with_retry(&client, &url, &policy(), &breaker)
+                .await
+                .unwrap();
+        }
+        let result = get_with_retry(&client, &url, &policy(), &breaker).await;
+
+        assert!(matches!(result, Err(Error::CircuitOpen)));
+        assert_eq!(requests.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn breaker_lets_a_request_through_after_cooldown() {
+        let (url, _) = flaky_server(0).await;
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(10));
+        breaker.record_failure();
+        assert!(!breaker.allow());
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let response = get_with_retry(&reqwest::Client::new(), &url, &policy(), &breaker)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert!(breaker.allow());
+    }
+}
@@ -0,0 +1,19 @@
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::Client;
+use tokio::sync::OnceCell;
+
+static CLIENT: OnceCell<Client> = OnceCell::const_new();
+
+/// The one S3 client, shared so warm invocations don't load the AWS config again
+pub async fn client() -> &'static Client {
+    CLIENT
+        .get_or_init(|| async { Client::new(&aws_config::load_from_env().await) })
+        .await
+}
+
+/// Whether a conditional write lost to somebody else's: 412 when the object isn't what we
+/// expected, 409 when another write to it was still in flight
+pub fn lost_race<E>(e: &SdkError<E>) -> bool {
+    e.raw_response()
+        .is_some_and(|r| matches!(r.status().as_u16(), 409 | 412))
+}
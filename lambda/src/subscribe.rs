@@ -0,0 +1,75 @@
+use serde::Serialize;
+use url::form_urlencoded::byte_serialize;
+
+use crate::base_url::BaseUrl;
+
+fn encode(s: &str) -> String {
+    byte_serialize(s.as_bytes()).collect()
+}
+
+/// Prebuilt links for subscribing to a calendar (rather than importing it once)
+#[derive(Serialize, Debug, Eq, PartialEq)]
+pub struct SubscriptionLinks {
+    pub https: String,
+    pub webcal: String,
+    pub google: String,
+    pub outlook: String,
+    pub apple: String,
+}
+
+impl SubscriptionLinks {
+    pub fn new(base_url: &BaseUrl, id: &str) -> SubscriptionLinks {
+        let https = base_url.calendar_url(id);
+        let webcal = match https.split_once("://") {
+            Some((_, rest)) => format!("webcal://{rest}"),
+            None => format!("webcal://{https}"),
+        };
+        let google = format!(
+            "https://calendar.google.com/calendar/r?cid={}",
+            encode(&webcal)
+        );
+        let outlook = format!(
+            "https://outlook.live.com/calendar/0/addfromweb?url={}&name=Trashcal",
+            encode(&https)
+        );
+
+        SubscriptionLinks {
+            // Apple Calendar subscribes to anything behind webcal://
+            apple: webcal.clone(),
+            https,
+            webcal,
+            google,
+            outlook,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SubscriptionLinks;
+    use crate::base_url::BaseUrl;
+
+    #[test]
+    fn builds_links_from_base_url() {
+        let links = SubscriptionLinks::new(&BaseUrl::default(), "a4Ot0000001E8i4EAC");
+
+        assert_eq!(
+            links,
+            SubscriptionLinks {
+                https: "https://stabbylambda.com/trashcal/a4Ot0000001E8i4EAC".to_string(),
+                webcal: "webcal://stabbylambda.com/trashcal/a4Ot0000001E8i4EAC".to_string(),
+                google: "https://calendar.google.com/calendar/r?cid=webcal%3A%2F%2Fstabbylambda.com%2Ftrashcal%2Fa4Ot0000001E8i4EAC".to_string(),
+                outlook: "https://outlook.live.com/calendar/0/addfromweb?url=https%3A%2F%2Fstabbylambda.com%2Ftrashcal%2Fa4Ot0000001E8i4EAC&name=Trashcal".to_string(),
+                apple: "webcal://stabbylambda.com/trashcal/a4Ot0000001E8i4EAC".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn builds_links_from_configured_base_url() {
+        let links = SubscriptionLinks::new(&BaseUrl::new("http://localhost:9000"), "foo");
+
+        assert_eq!(links.https, "http://localhost:9000/foo");
+        assert_eq!(links.webcal, "webcal://localhost:9000/foo");
+    }
+}
@@ -0,0 +1,100 @@
+//! Where spans and logs go. Logs always go to stdout for CloudWatch. With the `otel` feature and
+//! `OTEL_EXPORTER_OTLP_ENDPOINT` set, spans are also exported over OTLP/HTTP to a collector.
+
+/// The standard OpenTelemetry variable, so collectors and sidecars configure us like anything else
+pub const ENDPOINT_VAR: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+#[cfg(feature = "otel")]
+mod otel {
+    use std::sync::OnceLock;
+
+    use lambda_runtime::tracing::{subscriber, warn};
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::SpanExporter;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use opentelemetry_sdk::Resource;
+    use subscriber::filter::{EnvFilter, LevelFilter};
+    use subscriber::layer::SubscriberExt;
+    use subscriber::util::SubscriberInitExt;
+    use subscriber::Layer;
+
+    static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+    /// The same level and format rules as `lambda_runtime::tracing::init_default_subscriber()`
+    fn log_layer<S>() -> Box<dyn Layer<S> + Send + Sync>
+    where
+        S: tracing::Subscriber + for<'a> subscriber::registry::LookupSpan<'a>,
+    {
+        let layer = subscriber::fmt::layer().with_target(false).without_time();
+        let json = std::env::var("AWS_LAMBDA_LOG_FORMAT")
+            .is_ok_and(|format| format.eq_ignore_ascii_case("json"));
+        if json {
+            layer.json().boxed()
+        } else {
+            layer.boxed()
+        }
+    }
+
+    fn env_filter() -> EnvFilter {
+        let level = std::env::var("AWS_LAMBDA_LOG_LEVEL")
+            .or_else(|_| std::env::var("RUST_LOG"))
+            .ok()
+            .and_then(|level| level.parse().ok())
+            .unwrap_or(LevelFilter::INFO);
+        EnvFilter::builder()
+            .with_default_directive(level.into())
+            .from_env_lossy()
+    }
+
+    /// Sets up logging plus OTLP export, or says why it couldn't
+    pub(super) fn init(service_name: &'static str) -> anyhow::Result<()> {
+        // picks up the endpoint (and any headers) from the standard OTEL_ variables
+        let exporter = SpanExporter::builder().with_http().build()?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name(service_name).build())
+            .build();
+        let tracer = provider.tracer(service_name);
+
+        subscriber::registry()
+            .with(env_filter())
+            .with(log_layer())
+            .with(tracing_opentelemetry::layer().with_tracer(tracer))
+            .try_init()?;
+        let _ = PROVIDER.set(provider);
+        Ok(())
+    }
+
+    pub(super) fn flush() {
+        if let Some(provider) = PROVIDER.get() {
+            if let Err(e) = provider.force_flush() {
+                warn!(message = "Couldn't export spans", error = %e);
+            }
+        }
+    }
+
+    pub(super) fn init_or_default(service_name: &'static str) {
+        if let Err(e) = init(service_name) {
+            lambda_runtime::tracing::init_default_subscriber();
+            warn!(message = "Couldn't set up OpenTelemetry", error = %e);
+        }
+    }
+}
+
+/// Sets up tracing for one of our binaries. Call once, at startup.
+pub fn init(service_name: &'static str) {
+    #[cfg(feature = "otel")]
+    if std::env::var_os(ENDPOINT_VAR).is_some() {
+        return otel::init_or_default(service_name);
+    }
+
+    let _ = service_name;
+    lambda_runtime::tracing::init_default_subscriber();
+}
+
+/// Pushes out any spans still waiting in the batch. Lambda freezes the process as soon as the
+/// handler returns, so this has to happen at the end of every invocation, not just at shutdown.
+pub fn flush() {
+    #[cfg(feature = "otel")]
+    otel::flush();
+}
@@ -1,31 +1,330 @@
-use anyhow::{ensure, Result};
-use lambda_runtime::tracing::info;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use http::header::RETRY_AFTER;
+use http::StatusCode;
+use lambda_runtime::tracing::warn;
+use reqwest::{Client, Response};
 use scraper::Html;
+use tokio::time::timeout;
+use tracing::{field, instrument, Span};
+
+use crate::changes::HistoryStore;
+use crate::collection_id::CollectionId;
+use crate::metrics::Metrics;
+use crate::page::PageKind;
+use crate::redact;
+use crate::retry::{get_with_retry, CircuitBreaker, RetryPolicy};
+use crate::{client::client, error::Error, pickup_calendar::PickupCalendar};
+
+static BREAKER: LazyLock<CircuitBreaker> = LazyLock::new(CircuitBreaker::default);
+
+/// How long a calendar request waits on history, on top of however long the city took
+const HISTORY_TIMEOUT: Duration = Duration::from_secs(2);
+
+// The last good calendar for each ID, for when the city is down
+static LAST_GOOD: LazyLock<Mutex<LastGood>> = LazyLock::new(|| Mutex::new(LastGood::new(1000)));
+
+/// The last good calendars for up to `capacity` IDs. When it's full, the one that's gone the
+/// longest without a fresh scrape makes room.
+#[derive(Debug)]
+struct LastGood {
+    capacity: usize,
+    calendars: HashMap<CollectionId, PickupCalendar>,
+    // oldest scrape first
+    order: VecDeque<CollectionId>,
+}
+
+impl LastGood {
+    fn new(capacity: usize) -> LastGood {
+        LastGood {
+            capacity,
+            calendars: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn get(&self, id: &CollectionId) -> Option<PickupCalendar> {
+        self.calendars.get(id).cloned()
+    }
+
+    fn insert(&mut self, id: &CollectionId, calendar: PickupCalendar) {
+        if self.calendars.insert(id.clone(), calendar).is_some() {
+            self.order.retain(|other| other != id);
+        }
+        self.order.push_back(id.clone());
+
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.calendars.remove(&oldest);
+            }
+        }
+    }
+}
 
-use crate::{error::Error, pickup_calendar::PickupCalendar};
+// Retry-After is either a number of seconds or an HTTP date
+fn retry_after(response: &Response) -> Option<u64> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse().ok().or_else(|| {
+        let date = DateTime::parse_from_rfc2822(value).ok()?;
+        let seconds = (date.to_utc() - Utc::now()).num_seconds();
+        Some(seconds.max(0).unsigned_abs())
+    })
+}
+
+// Gets the raw page, retrying transient failures. Error pages never make it to the parser.
+#[instrument(
+    name = "fetch",
+    skip_all,
+    fields(url = %redact::url(url), status = field::Empty, bytes = field::Empty, latency_ms = field::Empty)
+)]
+pub async fn fetch_with(
+    client: &Client,
+    url: &str,
+    policy: &RetryPolicy,
+    breaker: &CircuitBreaker,
+) -> Result<String, Error> {
+    let started = Instant::now();
+    let response = get_with_retry(client, url, policy, breaker).await?;
+    let span = Span::current();
+    span.record("status", response.status().as_u16());
+
+    let result = match response.status() {
+        status if status.is_success() => response.text().await.map_err(Error::from),
+        StatusCode::TOO_MANY_REQUESTS => Err(Error::RateLimited {
+            retry_after: retry_after(&response),
+        }),
+        status => Err(Error::UpstreamUnavailable(status)),
+    };
+    span.record("latency_ms", started.elapsed().as_millis() as u64);
+    if let Ok(body) = &result {
+        span.record("bytes", body.len());
+    }
+    result
+}
+
+// Gets the raw page with the shared retry policy and circuit breaker
+pub async fn fetch(client: &Client, url: &str) -> Result<String, Error> {
+    fetch_with(client, url, &RetryPolicy::default(), &BREAKER).await
+}
 
 // Gets a trash calendar given an ID
-pub async fn trashcal(id: &str) -> Result<PickupCalendar> {
-    // as far as I can tell, all IDs start with a4O
-    ensure!(id.starts_with("a4O"), Error::IdError(id.to_string()));
+#[instrument(skip_all, fields(collection_id = %redact::id(id.as_str())))]
+pub async fn trashcal(id: &CollectionId) -> Result<PickupCalendar> {
+    match scrape(id).await {
+        Ok(calendar) => {
+            LAST_GOOD.lock().unwrap().insert(id, calendar.clone());
+
+            // every fresh scrape is a chance to notice the city changed the schedule, but
+            // nobody's calendar should fail or wait long over it (boxed, since the S3 client's
+            // futures are deep enough to overflow the compiler's query depth)
+            let recorded = timeout(HISTORY_TIMEOUT, Box::pin(record_history(&calendar))).await;
+            match recorded {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => warn!(
+                    message = "Couldn't record history",
+                    error = %redact::text(&e.to_string(), id.as_str())
+                ),
+                Err(_) => warn!(message = "Couldn't record history", error = "timed out"),
+            }
+            Ok(calendar)
+        }
+        // a stale calendar beats no calendar, but only when the city let us down
+        Err(ScrapeError::Fetch(e)) => {
+            let cached = LAST_GOOD.lock().unwrap().get(id);
+            match cached {
+                Some(calendar) => {
+                    warn!(
+                        message = "Serving cached calendar",
+                        error = %redact::text(&e.to_string(), id.as_str())
+                    );
+                    Metrics::new().count("stale_served", 1).emit();
+                    Ok(calendar)
+                }
+                None => Err(e.into()),
+            }
+        }
+        Err(ScrapeError::Parse(e)) => Err(e),
+    }
+}
+
+async fn record_history(calendar: &PickupCalendar) -> Result<()> {
+    HistoryStore::from_env().await.record(calendar).await
+}
+
+/// Why a scrape failed: we never got the page, or we got something we couldn't use
+#[derive(Debug)]
+pub enum ScrapeError {
+    Fetch(Error),
+    Parse(anyhow::Error),
+}
 
-    // rip out the format suffix (.ics for Paul, .json for the website) before
-    // building the upstream URL
-    let id = id.replace(".ics", "").replace(".json", "");
+impl From<ScrapeError> for anyhow::Error {
+    fn from(e: ScrapeError) -> Self {
+        match e {
+            ScrapeError::Fetch(e) => e.into(),
+            ScrapeError::Parse(e) => e,
+        }
+    }
+}
+
+/// Where tests keep their copy of the city's site, if they've said so
+#[cfg(any(test, feature = "test-util"))]
+static UPSTREAM_OVERRIDE: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+/// Scrapes a copy of the city's site from somewhere else (like a test server) for the rest of
+/// the process. Only the first call counts.
+#[cfg(any(test, feature = "test-util"))]
+pub fn override_upstream_url(base: &str) {
+    let _ = UPSTREAM_OVERRIDE.set(base.to_string());
+}
 
-    info!("Getting trashcal");
-    let url = format!("https://getitdone.sandiego.gov/CollectionDetail?id={id}");
-    let html = reqwest::get(url).await?.text().await?;
+fn upstream_url(id: &CollectionId) -> url::Url {
+    #[cfg(any(test, feature = "test-util"))]
+    if let Some(url) = UPSTREAM_OVERRIDE
+        .get()
+        .and_then(|base| id.upstream_url_at(base).ok())
+    {
+        return url;
+    }
+    id.upstream_url()
+}
 
-    // If we got the landing page, don't even try to parse it
-    ensure!(
-        !html.contains("handleRedirect"),
-        Error::RedirectPage(id.to_string())
-    );
+// Fetches and parses a calendar straight from the city, with no cache to fall back on
+#[instrument(skip_all, fields(collection_id = %redact::id(id.as_str())))]
+pub async fn scrape(id: &CollectionId) -> Result<PickupCalendar, ScrapeError> {
+    let url = upstream_url(id);
+    let started = Instant::now();
+    let html = fetch(client(), url.as_str()).await;
+    Metrics::new()
+        .duration("upstream_latency", started.elapsed())
+        .emit();
+    let html = html.map_err(ScrapeError::Fetch)?;
 
-    info!("Parsing calendar");
+    // If we got anything but a collection detail page, don't even try to parse it
+    let started = Instant::now();
     let document = Html::parse_document(&html);
-    let calendar = PickupCalendar::try_from((id.as_str(), &document))?;
+    PageKind::classify(&document)
+        .ensure_collection_detail(id.as_str())
+        .map_err(|e| ScrapeError::Parse(e.into()))?;
+
+    let calendar = PickupCalendar::try_from((id.as_str(), &document))
+        .map_err(|e| ScrapeError::Parse(e.into()))?;
+    Metrics::new()
+        .duration("parse_duration", started.elapsed())
+        .count("pickups_parsed", calendar.pickups.len())
+        .emit();
 
     Ok(calendar)
 }
+
+// Gets a trash calendar given an ID that hasn't been validated yet
+pub async fn fetch_calendar(id: &str) -> Result<PickupCalendar> {
+    let id: CollectionId = id.parse()?;
+    trashcal(&id).await
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use http::StatusCode;
+
+    use super::{fetch_with, LastGood};
+    use crate::collection_id::CollectionId;
+    use crate::error::Error;
+    use crate::fixtures;
+    use crate::pickup_calendar::PickupCalendar;
+    use crate::retry::{CircuitBreaker, RetryPolicy};
+
+    /// Answers every request with the same canned response
+    async fn server(response: &'static str) -> String {
+        fixtures::server(move |_| response.to_string()).await.0
+    }
+
+    async fn fetch(url: &str) -> Result<String, Error> {
+        let policy = RetryPolicy {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            ..RetryPolicy::default()
+        };
+        fetch_with(
+            &reqwest::Client::new(),
+            url,
+            &policy,
+            &CircuitBreaker::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn server_error_is_upstream_unavailable() {
+        let url = server("HTTP/1.1 500 Internal Server Error\r\ncontent-length: 24\r\nconnection: close\r\n\r\n<html>handleRedirect</html>").await;
+
+        let result = fetch(&url).await;
+        assert!(
+            matches!(
+                result,
+                Err(Error::UpstreamUnavailable(
+                    StatusCode::INTERNAL_SERVER_ERROR
+                ))
+            ),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_limited_with_retry_after() {
+        let url = server("HTTP/1.1 429 Too Many Requests\r\nretry-after: 120\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").await;
+
+        let result = fetch(&url).await;
+        assert!(
+            matches!(
+                result,
+                Err(Error::RateLimited {
+                    retry_after: Some(120)
+                })
+            ),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn success_returns_body() {
+        let url =
+            server("HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok").await;
+
+        assert_eq!(fetch(&url).await.unwrap(), "ok");
+    }
+
+    fn calendar(id: &CollectionId) -> PickupCalendar {
+        PickupCalendar {
+            id: id.to_string(),
+            ..fixtures::calendar(&[])
+        }
+    }
+
+    #[test]
+    fn last_good_drops_the_stalest_calendar() {
+        let ids: Vec<CollectionId> = ["a4Ot0000001E8i4", "a4Ot0000001E8i5", "a4Ot0000001E8i6"]
+            .iter()
+            .map(|id| id.parse().unwrap())
+            .collect();
+        let mut last_good = LastGood::new(2);
+
+        last_good.insert(&ids[0], calendar(&ids[0]));
+        last_good.insert(&ids[1], calendar(&ids[1]));
+        // a fresh scrape moves the first one to the back of the line
+        last_good.insert(&ids[0], calendar(&ids[0]));
+        last_good.insert(&ids[2], calendar(&ids[2]));
+
+        assert_eq!(last_good.get(&ids[0]), Some(calendar(&ids[0])));
+        assert_eq!(last_good.get(&ids[1]), None);
+        assert_eq!(last_good.get(&ids[2]), Some(calendar(&ids[2])));
+        assert_eq!(last_good.order.len(), 2);
+    }
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::{Days, NaiveDate};
+use http::header::CACHE_CONTROL;
+use http::StatusCode;
+use trashcal::next::today;
+use trashcal::trashcal::override_upstream_url;
+use trashcal::trashcal_handler;
+
+/// How many times the canary went to the city
+static SCRAPES: AtomicUsize = AtomicUsize::new(0);
+
+fn city_date(date: NaiveDate) -> String {
+    date.format("%m/%d/%Y").to_string()
+}
+
+/// The collection detail page, with its pickups moved up to tomorrow and the week after so the
+/// canary has something in its window
+fn fresh_page() -> String {
+    let tomorrow = today().checked_add_days(Days::new(1)).unwrap();
+    let next_week = tomorrow.checked_add_days(Days::new(7)).unwrap();
+    include_str!("./data/pages/collection_detail.html")
+        .replace("01/02/2023", &city_date(tomorrow))
+        .replace("01/09/2023", &city_date(next_week))
+}
+
+async fn run_canary() -> (StatusCode, String, String) {
+    let input = include_str!("./data/canary.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let cache_control = response.headers()[CACHE_CONTROL].to_str().unwrap();
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    (
+        response.status(),
+        cache_control.to_string(),
+        body.to_string(),
+    )
+}
+
+// one test, since the report is shared by the whole process
+#[tokio::test]
+async fn canary_checks_the_window_once_a_minute() {
+    let city = common::city(|_| {
+        SCRAPES.fetch_add(1, Ordering::SeqCst);
+        fresh_page()
+    });
+    override_upstream_url(&city.await);
+
+    let (status, cache_control, body) = run_canary().await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(cache_control, "max-age=60");
+    assert!(body.contains("\"ok\": true"));
+    assert!(body.contains("\"missing\": []"));
+    assert_eq!(SCRAPES.load(Ordering::SeqCst), 1);
+
+    // asking again right away gets the same report without bothering the city
+    let (status, cache_control, again) = run_canary().await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(cache_control.starts_with("max-age="));
+    assert_eq!(again, body);
+    assert_eq!(SCRAPES.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,75 @@
+//! Fake servers for the integration tests, so each test file doesn't grow its own copy
+
+// every test binary gets its own copy of this module and uses a different part of it
+#![allow(dead_code)]
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A 200 with an HTML `page`
+pub fn html(page: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\ncontent-type: text/html\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{page}",
+        page.len()
+    )
+}
+
+/// An empty 200
+pub fn ok() -> String {
+    "HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_string()
+}
+
+/// Reads a whole request (the headers and as much body as they promise) as text
+async fn read_request(socket: &mut tokio::net::TcpStream) -> String {
+    let mut request = Vec::new();
+    let mut buf = [0; 4096];
+
+    loop {
+        let n = socket.read(&mut buf).await.unwrap();
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request);
+        if let Some((headers, body)) = text.split_once("\r\n\r\n") {
+            let length = headers
+                .lines()
+                .find_map(|l| {
+                    l.to_lowercase()
+                        .strip_prefix("content-length: ")
+                        .map(str::to_string)
+                })
+                .and_then(|l| l.parse::<usize>().ok())
+                .unwrap_or(0);
+            if body.len() >= length {
+                break;
+            }
+        }
+        if n == 0 {
+            break;
+        }
+    }
+
+    String::from_utf8_lossy(&request).to_string()
+}
+
+/// Answers every request with whatever `respond` makes of it, and returns the URL of `path` on
+/// the server
+pub async fn server(path: &str, respond: impl Fn(&str) -> String + Send + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/{path}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let request = read_request(&mut socket).await;
+            socket
+                .write_all(respond(&request).as_bytes())
+                .await
+                .unwrap();
+        }
+    });
+    url
+}
+
+/// Plays the city, answering each request with the page `page` picks for it. Pass the URL to
+/// `override_upstream_url`.
+pub async fn city(page: impl Fn(&str) -> String + Send + 'static) -> String {
+    server("CollectionDetail", move |request| html(&page(request))).await
+}
@@ -0,0 +1,109 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::NaiveDate;
+use lettre::{AsyncSmtpTransport, Tokio1Executor};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use trashcal::base_url::BaseUrl;
+use trashcal::digest::{send_digest, DigestSubscription};
+use trashcal::pickup::{Pickup, PickupType};
+use trashcal::pickup_calendar::PickupCalendar;
+
+/// Every message body the sink accepted
+type Received = Arc<Mutex<Vec<String>>>;
+
+/// Just enough SMTP to accept mail from lettre and hang on to it
+async fn smtp_sink() -> (String, Received) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("smtp://{}", listener.local_addr().unwrap());
+    let received: Received = Default::default();
+
+    let log = received.clone();
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read, mut write) = socket.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                let command = line.to_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                    b"250 sink\r\n"
+                } else if command.starts_with("DATA") {
+                    write.write_all(b"354 go ahead\r\n").await.unwrap();
+                    let mut data = String::new();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if line == "." {
+                            break;
+                        }
+                        data.push_str(&line);
+                        data.push('\n');
+                    }
+                    log.lock().unwrap().push(data);
+                    b"250 queued\r\n"
+                } else if command.starts_with("QUIT") {
+                    write.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                write.write_all(reply).await.unwrap();
+            }
+        }
+    });
+
+    (url, received)
+}
+
+fn calendar() -> PickupCalendar {
+    let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    PickupCalendar {
+        id: "a4Ot0000001E8i4EAC".to_string(),
+        address: "1234 AGATE ST".to_string(),
+        pickups: vec![
+            Pickup {
+                name: PickupType::Recyclables,
+                date,
+            },
+            Pickup {
+                name: PickupType::Trash,
+                date,
+            },
+        ],
+    }
+}
+
+#[tokio::test]
+async fn sends_digest_with_attachment() {
+    let (url, received) = smtp_sink().await;
+    let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(&url)
+        .unwrap()
+        .build();
+    let subscription = DigestSubscription {
+        id: "a4Ot0000001E8i4EAC".to_string(),
+        to: "me@example.com".to_string(),
+        whimsy: false,
+    };
+
+    send_digest(
+        &transport,
+        &BaseUrl::default(),
+        "Trashcal <trashcal@example.com>".parse().unwrap(),
+        &subscription,
+        &calendar(),
+        NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+    )
+    .await
+    .expect("Failed to send");
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    let message = &received[0];
+    assert!(message.contains("Subject: Trashcal: the week of January 1"));
+    assert!(message.contains("To: me@example.com"));
+    assert!(message.contains("Content-Type: text/plain"));
+    assert!(message.contains("Content-Type: text/html"));
+    assert!(message.contains("Monday, January 2: Recyclables, Trash"));
+    assert!(message.contains("filename=\"trashcal.ics\""));
+}
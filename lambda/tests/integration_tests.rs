@@ -1,7 +1,11 @@
-use http::header::CONTENT_TYPE;
+use http::header::{
+    ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ALLOW,
+    CACHE_CONTROL, CONTENT_TYPE, LOCATION,
+};
+use http::StatusCode;
 use std::sync::Once;
-use trashcal::trashcal_handler;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use trashcal::trashcal_handler;
 
 static INIT: Once = Once::new();
 
@@ -28,7 +32,8 @@ async fn path_based() {
         response.headers()[CONTENT_TYPE],
         "text/calendar;charset=UTF-8"
     );
-    assert!(body.contains("X-WR-CALNAME:Trashcal"));
+    assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    assert!(body.contains("X-WR-CALNAME:Trashcal – 1234 Agate St"));
 }
 
 #[tokio::test]
@@ -45,6 +50,18 @@ async fn path_based_with_extension() {
     assert!(body.contains("X-WR-CALNAME:Trashcal"));
 }
 
+#[tokio::test]
+async fn path_based_with_geo() {
+    init_tracing();
+    let input = include_str!("./data/path_based_with_geo.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body.contains("LOCATION:1234 Agate St"));
+    assert!(body.contains("GEO:32.798765;-117.2"));
+}
+
 #[tokio::test]
 async fn query_based() {
     init_tracing();
@@ -67,6 +84,7 @@ async fn path_based_with_json() {
     let response = trashcal_handler(request).await.expect("Failed to execute");
     let body = std::str::from_utf8(response.body()).expect("Should have a body");
     assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+    assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
     assert!(body.contains("1234 AGATE ST"));
 }
 
@@ -87,3 +105,252 @@ async fn fails_with_bad_id() {
     let response = trashcal_handler(request).await;
     assert!(response.is_err())
 }
+
+#[tokio::test]
+async fn path_based_with_html() {
+    init_tracing();
+    let input = include_str!("./data/path_based_with_html.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/html;charset=UTF-8");
+    assert!(body.contains("1234 AGATE ST"));
+    assert!(body.contains("webcal://"));
+}
+
+#[tokio::test]
+async fn subscribe_redirects_to_webcal() {
+    init_tracing();
+    let input = include_str!("./data/subscribe.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    assert_eq!(response.status(), StatusCode::FOUND);
+    assert_eq!(
+        response.headers()[LOCATION],
+        "webcal://stabbylambda.com/trashcal/a4Ot0000001E8i4EAC"
+    );
+}
+
+#[tokio::test]
+async fn subscribe_with_json() {
+    init_tracing();
+    let input = include_str!("./data/subscribe_with_json.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+    assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    assert!(body.contains("https://calendar.google.com/calendar/r?cid=webcal"));
+    assert!(body.contains("outlook.live.com"));
+}
+
+#[tokio::test]
+async fn next_pickup() {
+    init_tracing();
+    let input = include_str!("./data/next.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+    assert!(body.contains("\"days_until\""));
+}
+
+#[tokio::test]
+async fn next_pickup_with_text() {
+    init_tracing();
+    let input = include_str!("./data/next_with_text.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/plain;charset=UTF-8");
+    assert!(body.contains("Trash"));
+}
+
+#[tokio::test]
+async fn path_based_with_csv() {
+    init_tracing();
+    let input = include_str!("./data/path_based_with_csv.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/csv;charset=UTF-8");
+    assert!(body.starts_with("date,weekday,type,address"));
+    assert!(body.contains("1234 AGATE ST"));
+}
+
+#[tokio::test]
+async fn path_based_with_txt() {
+    init_tracing();
+    let input = include_str!("./data/path_based_with_txt.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/plain;charset=UTF-8");
+    assert!(body.contains("1234 AGATE ST"));
+}
+
+#[tokio::test]
+async fn path_based_with_atom() {
+    init_tracing();
+    let input = include_str!("./data/path_based_with_atom.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(
+        response.headers()[CONTENT_TYPE],
+        "application/atom+xml;charset=UTF-8"
+    );
+    assert!(body.contains("<entry>"));
+}
+
+#[tokio::test]
+async fn path_based_with_rss() {
+    init_tracing();
+    let input = include_str!("./data/path_based_with_rss.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(
+        response.headers()[CONTENT_TYPE],
+        "application/rss+xml;charset=UTF-8"
+    );
+    assert!(body.contains("<item>"));
+}
+
+#[tokio::test]
+async fn schedule_changes() {
+    init_tracing();
+    let input = include_str!("./data/changes.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+    assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+    assert!(body.starts_with('['));
+}
+
+#[tokio::test]
+async fn unknown_extension_is_not_found() {
+    init_tracing();
+    let input = include_str!("./data/path_based_with_unknown_extension.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn unknown_sub_path_is_not_found() {
+    init_tracing();
+    let input = include_str!("./data/unknown_sub_path.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn format_override_redirects_to_suffix() {
+    init_tracing();
+    let input = include_str!("./data/format_override.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    assert_eq!(response.status(), StatusCode::FOUND);
+    assert_eq!(
+        response.headers()[LOCATION],
+        "https://stabbylambda.com/trashcal/a4Ot0000001E8i4EAC.json?whimsy=false"
+    );
+    assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+}
+
+#[tokio::test]
+async fn unknown_format_override_is_not_acceptable() {
+    init_tracing();
+    let input = include_str!("./data/format_override_unknown.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+}
+
+#[tokio::test]
+async fn non_canonical_options_redirect() {
+    init_tracing();
+    let input = include_str!("./data/non_canonical_options.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    assert_eq!(response.status(), StatusCode::FOUND);
+    assert_eq!(
+        response.headers()[LOCATION],
+        "https://stabbylambda.com/trashcal/a4Ot0000001E8i4EAC.json?lang=es&whimsy=false"
+    );
+}
+
+#[tokio::test]
+async fn bad_option_is_bad_request() {
+    init_tracing();
+    let input = include_str!("./data/bad_option.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(body.contains("types"));
+}
+
+#[tokio::test]
+async fn cors_preflight() {
+    init_tracing();
+    let input = include_str!("./data/preflight.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    assert_eq!(
+        response.headers()[ACCESS_CONTROL_ALLOW_METHODS],
+        "GET, HEAD, OPTIONS"
+    );
+    assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_HEADERS], "accept");
+    assert!(response.body().is_empty());
+}
+
+#[tokio::test]
+async fn head_has_headers_but_no_body() {
+    init_tracing();
+    let input = include_str!("./data/head.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+    assert!(response.body().is_empty());
+}
+
+#[tokio::test]
+async fn other_methods_are_not_allowed() {
+    init_tracing();
+    let input = include_str!("./data/delete.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers()[ALLOW], "GET, HEAD, OPTIONS");
+}
+
+#[tokio::test]
+async fn healthz() {
+    init_tracing();
+    let input = include_str!("./data/healthz.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+    assert!(body.contains("\"status\":\"ok\""));
+}
+
+#[tokio::test]
+#[ignore = "hits the real city site; canary_tests.rs covers it against a fixture"]
+async fn canary() {
+    init_tracing();
+    let input = include_str!("./data/canary.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    let response = trashcal_handler(request).await.expect("Failed to execute");
+    let body = std::str::from_utf8(response.body()).expect("Should have a body");
+    assert_eq!(response.status(), StatusCode::OK, "{body}");
+    assert!(body.contains("\"ok\": true"));
+}
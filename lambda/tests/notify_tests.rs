@@ -0,0 +1,86 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use chrono::NaiveDate;
+use trashcal::notify::{Delivery, Notifier, Reminder, WebhookFormat, WebhookSubscription};
+use trashcal::pickup::{Pickup, PickupType};
+use trashcal::pickup_calendar::PickupCalendar;
+
+/// Every request the mock receiver got, as raw HTTP
+type Received = Arc<Mutex<Vec<String>>>;
+
+/// A tiny webhook receiver that records each request and answers 200
+async fn mock_receiver() -> (String, Received) {
+    let received: Received = Default::default();
+    let log = received.clone();
+    let url = common::server("hook", move |request| {
+        log.lock().unwrap().push(request.to_string());
+        common::ok()
+    })
+    .await;
+
+    (url, received)
+}
+
+fn reminder() -> Reminder {
+    let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    let calendar = PickupCalendar {
+        id: "a4Ot0000001E8i4EAC".to_string(),
+        address: "1234 AGATE ST".to_string(),
+        pickups: vec![
+            Pickup {
+                name: PickupType::Recyclables,
+                date,
+            },
+            Pickup {
+                name: PickupType::Trash,
+                date,
+            },
+        ],
+    };
+
+    Reminder::for_tomorrow(&calendar, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn posts_slack_reminder() {
+    let (url, received) = mock_receiver().await;
+    let subscription = WebhookSubscription {
+        id: "a4Ot0000001E8i4EAC".to_string(),
+        url,
+        format: WebhookFormat::Slack,
+        whimsy: false,
+    };
+
+    let notifier = Notifier::new();
+    let delivery = notifier.notify(&subscription, &reminder()).await.unwrap();
+    assert_eq!(delivery, Delivery::Sent);
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    let request = received[0].to_lowercase();
+    assert!(request.starts_with("post /hook"));
+    assert!(request.contains("idempotency-key: a4ot0000001e8i4eac:2023-01-02"));
+    assert!(received[0]
+        .contains(r#"{"text":"Tomorrow is trash day at 1234 AGATE ST: Recyclables, Trash"}"#));
+}
+
+#[tokio::test]
+async fn only_posts_once_per_date() {
+    let (url, received) = mock_receiver().await;
+    let subscription = WebhookSubscription {
+        id: "a4Ot0000001E8i4EAC".to_string(),
+        url,
+        format: WebhookFormat::Json,
+        whimsy: true,
+    };
+
+    let notifier = Notifier::new();
+    let first = notifier.notify(&subscription, &reminder()).await.unwrap();
+    let second = notifier.notify(&subscription, &reminder()).await.unwrap();
+
+    assert_eq!(first, Delivery::Sent);
+    assert_eq!(second, Delivery::AlreadySent);
+    assert_eq!(received.lock().unwrap().len(), 1);
+}
@@ -0,0 +1,72 @@
+#![cfg(feature = "otel")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use trashcal::redact;
+use trashcal::telemetry::{self, ENDPOINT_VAR};
+use trashcal::trashcal_handler;
+
+/// A stand-in OTLP/HTTP collector that hands back the request line and body of every export
+fn collector() -> (String, mpsc::Receiver<(String, Vec<u8>)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for socket in listener.incoming() {
+            let mut socket = socket.unwrap();
+            let mut reader = BufReader::new(socket.try_clone().unwrap());
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                .unwrap();
+            let _ = tx.send((request_line.trim().to_string(), body));
+        }
+    });
+    (endpoint, rx)
+}
+
+#[tokio::test]
+async fn exports_spans_to_collector() {
+    let (endpoint, exports) = collector();
+    std::env::set_var(ENDPOINT_VAR, endpoint);
+    telemetry::init("trashcal-test");
+
+    let input = include_str!("./data/subscribe_with_json.json");
+    let request = lambda_http::request::from_str(input).expect("failed to create request");
+    trashcal_handler(request).await.expect("Failed to execute");
+    telemetry::flush();
+
+    let (request_line, body) = exports
+        .recv_timeout(Duration::from_secs(10))
+        .expect("collector never heard from us");
+    assert_eq!(request_line, "POST /v1/traces HTTP/1.1");
+
+    // protobuf keeps strings as-is, so the span and its (hashed) collection ID are right there
+    let body = String::from_utf8_lossy(&body);
+    assert!(body.contains("subscribe"));
+    assert!(body.contains("collection_id"));
+    assert!(body.contains(&redact::id("a4Ot0000001E8i4EAC")));
+    assert!(!body.contains("a4Ot0000001E8i4EAC"));
+    assert!(body.contains("trashcal-test"));
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use http::StatusCode;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use trashcal::redact;
+use trashcal::trashcal::override_upstream_url;
+use trashcal::trashcal_handler;
+
+/// Everything that got logged, for checking what made it out
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Captured {
+    type Writer = Captured;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+impl Captured {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).to_string()
+    }
+}
+
+/// An ID the city doesn't know about, so it sends us to the lookup page
+const UNKNOWN_ID: &str = "a4Ot0000001E8iA";
+
+/// The lookup page for `UNKNOWN_ID` and the same collection detail page for everybody else
+fn page(request: &str) -> String {
+    if request.contains(UNKNOWN_ID) {
+        include_str!("./data/pages/lookup_landing.html").to_string()
+    } else {
+        include_str!("./data/pages/collection_detail.html").to_string()
+    }
+}
+
+#[tokio::test]
+async fn logs_never_have_full_addresses() {
+    let logs = Captured::default();
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::new("trashcal=debug"))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(logs.clone()),
+        )
+        .init();
+    override_upstream_url(&common::city(page).await);
+
+    for input in [
+        include_str!("./data/path_based.json"),
+        include_str!("./data/path_based_with_geo.json"),
+        include_str!("./data/path_based_with_json.json"),
+        include_str!("./data/next.json"),
+    ] {
+        let request = lambda_http::request::from_str(input).expect("failed to create request");
+        let response = trashcal_handler(request).await.expect("Failed to execute");
+        assert_ne!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    // the lambda runtime logs whatever errors make it out of the handler
+    let bad_id = include_str!("./data/bad_id.json");
+    let unknown_id =
+        include_str!("./data/path_based.json").replace("a4Ot0000001E8i4EAC", UNKNOWN_ID);
+    for (input, id) in [
+        (bad_id.to_string(), "a4OtZZZZZZZZ"),
+        (unknown_id, UNKNOWN_ID),
+    ] {
+        let request = lambda_http::request::from_str(&input).expect("failed to create request");
+        let error = trashcal_handler(request)
+            .await
+            .expect_err("should fail")
+            .to_string();
+        assert!(error.contains(&redact::id(id)), "{error}");
+        assert!(!error.contains(id), "{error}");
+    }
+
+    let logs = logs.contents();
+    assert!(logs.contains("Returning calendar"), "{logs}");
+    assert!(logs.contains("Agate St, 92109"), "{logs}");
+    assert!(logs.contains(&redact::id("a4Ot0000001E8i4EAC")), "{logs}");
+    assert!(!logs.contains("1234 AGATE ST"), "{logs}");
+    assert!(!logs.contains("a4Ot0000001E8i4EAC"), "{logs}");
+    assert!(!logs.contains("32.798765"), "{logs}");
+    assert!(!logs.contains(UNKNOWN_ID), "{logs}");
+}